@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::math::vec_3d::Vec3d;
+use crate::math::vec_4d::Vec4d;
+use crate::triangle::Triangle;
+
+pub(crate) type VertexKey = (i64, i64, i64);
+
+// vertices closer than ~1e-6 share the same key
+pub(crate) fn vertex_key(p: &Vec4d) -> VertexKey {
+    const SCALE: f64 = 1e6;
+    (
+        (p.x() * SCALE).round() as i64,
+        (p.y() * SCALE).round() as i64,
+        (p.z() * SCALE).round() as i64,
+    )
+}
+
+// undirected edge, smaller vertex key first
+pub type Edge = (VertexKey, VertexKey);
+
+pub(crate) fn edge(a: &Vec4d, b: &Vec4d) -> Edge {
+    let (a, b) = (vertex_key(a), vertex_key(b));
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// Edge -> triangles map of a triangle list. Vertices are matched by position,
+// so triangles sharing an edge don't have to share vertex instances.
+pub struct Adjacency {
+    edges: HashMap<Edge, Vec<usize>>,
+    triangle_edges: Vec<[Edge; 3]>,
+    normals: Vec<Vec3d>,
+}
+
+impl Adjacency {
+    pub fn build(triangles: &[Triangle]) -> Adjacency {
+        let mut edges: HashMap<Edge, Vec<usize>> = HashMap::new();
+        let mut triangle_edges = Vec::with_capacity(triangles.len());
+        let normals = triangles.iter().map(|t| t.normal()).collect();
+
+        for (i, tri) in triangles.iter().enumerate() {
+            let p = tri.points();
+            let tri_edges = [edge(&p[0], &p[1]), edge(&p[1], &p[2]), edge(&p[2], &p[0])];
+            for e in tri_edges.iter() {
+                edges.entry(*e).or_default().push(i);
+            }
+            triangle_edges.push(tri_edges);
+        }
+
+        Adjacency {
+            edges,
+            triangle_edges,
+            normals,
+        }
+    }
+
+    // every edge with the indices of the triangles using it,
+    // two for manifold edges, one for border edges, more for non-manifold ones
+    pub fn edges(&self) -> &HashMap<Edge, Vec<usize>> {
+        &self.edges
+    }
+
+    pub fn edge_triangles(&self, a: &Vec4d, b: &Vec4d) -> &[usize] {
+        self.edges.get(&edge(a, b)).map_or(&[], |t| t.as_slice())
+    }
+
+    // edges of triangle i in p0-p1, p1-p2, p2-p0 order
+    pub fn triangle_edges(&self, i: usize) -> &[Edge; 3] {
+        &self.triangle_edges[i]
+    }
+
+    // Angle between the two faces sharing the edge, in radians: PI for coplanar faces,
+    // PI / 2 for the edges of a cube. NaN unless exactly two triangles share the edge.
+    pub fn dihedral_angle(&self, edge: &Edge) -> f64 {
+        match self.edges.get(edge).map(|t| t.as_slice()) {
+            Some(&[a, b]) => std::f64::consts::PI - self.normals[a].dot(&self.normals[b]).clamp(-1.0, 1.0).acos(),
+            _ => f64::NAN,
+        }
+    }
+
+    // triangles sharing an edge with triangle i
+    pub fn neighbors(&self, i: usize) -> Vec<usize> {
+        let mut res: Vec<usize> = self.triangle_edges[i]
+            .iter()
+            .flat_map(|e| self.edges[e].iter().copied())
+            .filter(|&j| j != i)
+            .collect();
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use macroquad::prelude::WHITE;
+
+    use crate::math::vec_4d::Vec4d;
+    use crate::object::{Cube, Mesh, ObjectNameTag};
+    use crate::triangle::Triangle;
+
+    use super::{edge, Adjacency};
+
+    #[test]
+    fn dihedral_angles() {
+        let cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        let adjacency = cube.adjacency();
+        // every cube face is split by a diagonal, those edges are flat
+        let (mut right, mut flat) = (0, 0);
+        for e in adjacency.edges().keys() {
+            let angle = adjacency.dihedral_angle(e);
+            if (angle - PI / 2.0).abs() < 1e-9 {
+                right += 1;
+            } else if (angle - PI).abs() < 1e-9 {
+                flat += 1;
+            }
+        }
+        assert_eq!((right, flat), (12, 6));
+
+        let corner = |x: f64, y: f64| Vec4d::new(x, y, 0.0, 1.0);
+        let quad = [
+            Triangle::new(WHITE, corner(0.0, 0.0), corner(1.0, 0.0), corner(1.0, 1.0)),
+            Triangle::new(WHITE, corner(0.0, 0.0), corner(1.0, 1.0), corner(0.0, 1.0)),
+        ];
+        let adjacency = Adjacency::build(&quad);
+        let diagonal = edge(&corner(0.0, 0.0), &corner(1.0, 1.0));
+        assert!((adjacency.dihedral_angle(&diagonal) - PI).abs() < 1e-9);
+        assert!(adjacency.dihedral_angle(&edge(&corner(0.0, 0.0), &corner(1.0, 0.0))).is_nan());
+    }
+}
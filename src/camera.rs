@@ -1,21 +1,147 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::f64::consts::PI;
 use std::rc::{self, Rc};
 
-use macroquad::prelude::Color;
+use macroquad::prelude::{Color, BLACK, BLANK, WHITE};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
+use crate::light::DirectionalLight;
+use crate::material::{lerp_color, posterize, CubeMap};
 use crate::math::matrix4x4::Matrix4x4;
 use crate::math::plane::Plane;
+use crate::math::vec_2d::Vec2d;
 use crate::math::vec_3d::Vec3d;
-use crate::object::{Mesh, Object, ObjectNameTag, ObjectStruct};
-use crate::triangle::Triangle;
+use crate::math::vec_4d::Vec4d;
+use crate::object::{Mesh, Object, ObjectNameTag, ObjectStruct, Pose};
+#[cfg(feature = "profiling")]
+use crate::profile::Profile;
+use crate::profile::{Profiler, Stage, Timer};
+use crate::raster::{FrameBuffer, ToneMap};
+use crate::triangle::{Triangle, TriangleTransform};
+
+// per triangle data handed to a custom shader, normal and centroid are in world space
+pub struct ShaderInput {
+    pub normal: Vec3d,
+    pub centroid: Vec3d,
+    pub base_color: Color,
+}
+
+pub type Shader = Box<dyn Fn(&ShaderInput) -> Color>;
+
+// run of consecutive same-colored triangles of the sorted buffer, drawn with one color setup
+pub struct DrawBatch<'a> {
+    pub color: Color,
+    pub triangles: &'a [Triangle],
+}
+
+// What the projected triangles are drawn as, by rasterize and by callers drawing the buffer themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Filled,
+    // edges only, see Triangle::edges
+    Wireframe,
+    FilledWireframe,
+}
+
+impl RenderMode {
+    pub fn fills(self) -> bool {
+        self != RenderMode::Wireframe
+    }
+
+    pub fn draws_edges(self) -> bool {
+        self != RenderMode::Filled
+    }
+
+    // the following mode, for flipping through them with a key
+    pub fn next(self) -> RenderMode {
+        match self {
+            RenderMode::Filled => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::FilledWireframe,
+            RenderMode::FilledWireframe => RenderMode::Filled,
+        }
+    }
+}
+
+// A point at every stage of the projection pipeline, see MyCamera::debug_project.
+#[derive(Debug, Clone)]
+pub struct ProjectionTrace {
+    pub world: Vec3d,
+    // relative to the camera, z along its look_at direction
+    pub view: Vec4d,
+    // after the projection matrix, before the divide by w
+    pub clip: Vec4d,
+    // x, y in [-1, 1] and z in [0, 1] for points inside the frustum
+    pub ndc: Vec4d,
+    // pixels, z stays the ndc depth
+    pub screen: Vec4d,
+}
+
+// light every face gets with set_lights, so faces turned away from all lights aren't black
+pub const LIGHT_AMBIENT: f64 = 0.15;
+
+// rotational shake in radians per unit of positional shake
+const SHAKE_ROTATION: f64 = 0.05;
+
+// transient random offset of the camera, fading out linearly over its duration
+#[derive(Clone)]
+struct Shake {
+    intensity: f64,
+    duration: f64,
+    elapsed: f64,
+    rng: StdRng,
+    position: Vec3d,
+    angle: Vec3d,
+}
+
+// Scratch buffers kept by the camera for the clip stage, so projecting in a steady state
+// allocates nothing once they have grown to the largest clipped triangle count.
+#[derive(Default)]
+struct ClipWorkspace {
+    clipped: Vec<Triangle>,
+    temp: Vec<Triangle>,
+    scissored: Vec<Triangle>,
+}
 
 pub struct MyCamera {
     obj: ObjectStruct,
     triangles: Vec<Triangle>,
+    #[cfg(not(feature = "parallel"))]
+    clip_workspace: RefCell<ClipWorkspace>,
     clip_planes: Vec<Plane>,
+    scissor_planes: Vec<Plane>,
+    environment: Option<CubeMap>,
+    shader: Option<Shader>,
+    lights: Vec<DirectionalLight>,
+    render_mode: RenderMode,
+    wireframe_width: f64,
+    hidden_line: bool,
+    cull_backfaces: bool,
+    dither: bool,
+    perspective_correct: bool,
+    clear_color: Color,
+    clear_depth: f64,
+    pixel_snap: bool,
+    tone_map: ToneMap,
+    exposure: f64,
+    posterize: u8,
+    progressive: u32,
+    progressive_pass: Cell<u32>,
+    progressive_frame: RefCell<Option<FrameBuffer>>,
+    shake: Option<Shake>,
+    shake_seed: u64,
+    profiler: Profiler,
+    width: i32,
+    height: i32,
+    fov: f64,
+    znear: f64,
+    zfar: f64,
     aspect: f64,
+    p: Matrix4x4,
     sp: Matrix4x4,
 }
 
@@ -24,128 +150,907 @@ impl MyCamera {
         Self {
             obj: ObjectStruct::new(ObjectNameTag::new(name)),
             triangles: vec![],
+            #[cfg(not(feature = "parallel"))]
+            clip_workspace: RefCell::default(),
             clip_planes: vec![],
+            scissor_planes: vec![],
+            environment: None,
+            shader: None,
+            lights: vec![],
+            render_mode: RenderMode::FilledWireframe,
+            wireframe_width: 0.0,
+            hidden_line: false,
+            cull_backfaces: true,
+            dither: false,
+            perspective_correct: true,
+            clear_color: BLANK,
+            clear_depth: f64::INFINITY,
+            pixel_snap: false,
+            tone_map: ToneMap::None,
+            exposure: 1.0,
+            posterize: 0,
+            progressive: 0,
+            progressive_pass: Cell::new(0),
+            progressive_frame: RefCell::new(None),
+            shake: None,
+            shake_seed: 0,
+            profiler: Profiler::default(),
+            width: 0,
+            height: 0,
+            fov: 0.0,
+            znear: 0.0,
+            zfar: 0.0,
             aspect: 0.0,
+            p: Matrix4x4::identity(),
             sp: Matrix4x4::identity(),
         }
     }
 
     pub fn init(&mut self, width: i32, height: i32, fov: f64, znear: f64, zfar: f64) {
+        self.width = width;
+        self.height = height;
+        self.fov = fov;
+        self.znear = znear;
+        self.zfar = zfar;
         self.aspect = width as f64 / height as f64;
-        let p = Matrix4x4::projection(fov, self.aspect, znear, zfar);
+        self.p = Matrix4x4::projection(fov, self.aspect, znear, zfar);
         let s = Matrix4x4::screen_space(width, height);
-        self.sp = s * p;
+        self.sp = s * self.p.clone();
 
-        self.clip_planes
-            .push(Plane::new(Vec3d::new(0.0, 0.0, 1.0), Vec3d::new(0.0, 0.0, znear)));
-        self.clip_planes
-            .push(Plane::new(Vec3d::new(0.0, 0.0, -1.0), Vec3d::new(0.0, 0.0, zfar)));
+        self.clip_planes = Self::frustum_planes(fov, self.aspect, znear, zfar);
+    }
+
+    // Camera with the same world pose, fov, clip distances and render settings (lights, tone mapping, ...)
+    // rendering into a width x height viewport (minimaps, thumbnails). Attached objects, the screen space
+    // scissor, the shader and the projected triangles are not carried over.
+    pub fn with_viewport(&self, width: i32, height: i32) -> MyCamera {
+        let mut camera = MyCamera::new(self.nametag().name());
+        camera.obj = self.obj.clone();
+        camera.obj.attached_objects.clear();
+        camera.environment = self.environment.clone();
+        camera.lights = self.lights.clone();
+        camera.render_mode = self.render_mode;
+        camera.wireframe_width = self.wireframe_width;
+        camera.hidden_line = self.hidden_line;
+        camera.cull_backfaces = self.cull_backfaces;
+        camera.dither = self.dither;
+        camera.perspective_correct = self.perspective_correct;
+        camera.clear_color = self.clear_color;
+        camera.clear_depth = self.clear_depth;
+        camera.pixel_snap = self.pixel_snap;
+        camera.tone_map = self.tone_map;
+        camera.exposure = self.exposure;
+        camera.posterize = self.posterize;
+        camera.progressive = self.progressive;
+        camera.shake = self.shake.clone();
+        camera.shake_seed = self.shake_seed;
+        camera.init(width, height, self.fov, self.znear, self.zfar);
+        camera
+    }
+
+    // Camera blended between this one (t = 0) and other (t = 1) for smooth cuts: position, fov and
+    // clip distances are lerped and the orientation is slerped. The viewport and the render settings
+    // (see with_viewport) are this camera's.
+    pub fn lerp(&self, other: &MyCamera, t: f64) -> MyCamera {
+        let mut camera = self.with_viewport(self.width, self.height);
+        camera.set_pose(&Pose::lerp(&self.pose(), &other.pose(), t));
+
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+        camera.init(
+            self.width,
+            self.height,
+            lerp(self.fov, other.fov),
+            lerp(self.znear, other.znear),
+            lerp(self.zfar, other.zfar),
+        );
+        camera
+    }
+
+    // view space clip planes: near, far, left, right, top, bottom
+    fn frustum_planes(fov: f64, aspect: f64, znear: f64, zfar: f64) -> Vec<Plane> {
+        let mut planes = Vec::with_capacity(6);
+
+        planes.push(Plane::new(Vec3d::new(0.0, 0.0, 1.0), Vec3d::new(0.0, 0.0, znear)));
+        planes.push(Plane::new(Vec3d::new(0.0, 0.0, -1.0), Vec3d::new(0.0, 0.0, zfar)));
 
         let thetta1 = PI * fov * 0.5 / 180.0;
-        let thetta2 = (self.aspect * thetta1.tan()).atan();
+        let thetta2 = (aspect * thetta1.tan()).atan();
 
-        self.clip_planes.push(Plane::new(
+        planes.push(Plane::new(
             Vec3d::new(-thetta2.cos(), 0.0, thetta2.sin()),
             Vec3d::new(0.0, 0.0, 0.0),
         ));
-        self.clip_planes.push(Plane::new(
+        planes.push(Plane::new(
             Vec3d::new(thetta2.cos(), 0.0, thetta2.sin()),
             Vec3d::new(0.0, 0.0, 0.0),
         ));
-        self.clip_planes.push(Plane::new(
+        planes.push(Plane::new(
             Vec3d::new(0.0, thetta1.cos(), thetta1.sin()),
             Vec3d::new(0.0, 0.0, 0.0),
         ));
-        self.clip_planes.push(Plane::new(
+        planes.push(Plane::new(
             Vec3d::new(0.0, -thetta1.cos(), thetta1.sin()),
             Vec3d::new(0.0, 0.0, 0.0),
         ));
+
+        planes
     }
 
-    pub fn sorted(&mut self) -> &Vec<Triangle> {
-        self.triangles.sort_by(|t1, t2| {
-            let mut v_z1 = vec![t1.points()[0].z(), t1.points()[1].z(), t1.points()[2].z()];
-            let mut v_z2 = vec![t2.points()[0].z(), t2.points()[1].z(), t2.points()[2].z()];
+    // clip planes transformed by the camera model, usable against world space geometry
+    pub fn world_clip_planes(&self) -> Vec<Plane> {
+        let model = self.model();
+
+        self.clip_planes
+            .iter()
+            .map(|plane| {
+                let normal = &model * plane.normal();
+                let point = Vec3d::from_vec4d(&(&model * &plane.point().make_point_4d()));
+                Plane::new(normal, point)
+            })
+            .collect()
+    }
+
+    // Restricts rendering to a convex screen space polygon (any winding).
+    // Projected triangles are clipped against its edges, an empty polygon turns scissor off.
+    pub fn set_scissor(&mut self, polygon: Vec<Vec2d>) {
+        self.scissor_planes.clear();
+
+        if polygon.len() < 3 {
+            return;
+        }
+
+        let signed_area: f64 = (0..polygon.len())
+            .map(|i| {
+                let a = &polygon[i];
+                let b = &polygon[(i + 1) % polygon.len()];
+                a.x() * b.y() - b.x() * a.y()
+            })
+            .sum();
+        let orientation = signed_area.signum();
+
+        for i in 0..polygon.len() {
+            let a = &polygon[i];
+            let b = &polygon[(i + 1) % polygon.len()];
+            let inward = Vec3d::new(-(b.y() - a.y()) * orientation, (b.x() - a.x()) * orientation, 0.0);
+            self.scissor_planes.push(Plane::new(inward, Vec3d::new(a.x(), a.y(), 0.0)));
+        }
+    }
+
+    // point the camera looks at, distance units in front of it
+    pub fn focus_point(&self, distance: f64) -> Vec3d {
+        self.position() + &(self.look_at() * distance)
+    }
+
+    // Turns the camera to face target, with up as the vertical hint (the camera never rolls away
+    // from it). The basis is set directly instead of accumulated rotations, and the tracked angles
+    // are reset to the matching pitch and yaw with no roll. When up is parallel to the view
+    // direction the world axis least aligned with it is used instead. A target at the camera
+    // position leaves it unchanged.
+    pub fn look_at_point(&mut self, target: &Vec3d, up: &Vec3d) {
+        let to_target = *target - *self.position();
+        if to_target.sqr_abs() < f64::EPSILON {
+            return;
+        }
+        let look_at = to_target.normalized();
+
+        let mut left = up.cross(&look_at);
+        if left.sqr_abs() < 1e-12 {
+            let axes = [Vec3d::new(1.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 0.0, 1.0)];
+            let fallback = axes.iter().min_by(|a, b| a.dot(&look_at).abs().total_cmp(&b.dot(&look_at).abs())).unwrap();
+            left = fallback.cross(&look_at);
+        }
+        let left = left.normalized();
+        let up = look_at.cross(&left);
+
+        *self.transform_matrix_mut() = Matrix4x4::from_array([
+            left.x(), left.y(), left.z(), 0.0,
+            up.x(), up.y(), up.z(), 0.0,
+            look_at.x(), look_at.y(), look_at.z(), 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ]);
+        *self.angle_left_up_look_at_mut() = Vec3d::new((-look_at.y()).asin(), look_at.x().atan2(look_at.z()), 0.0);
+    }
 
-            v_z1.sort_by(|a, b| a.partial_cmp(b).unwrap());
-            v_z2.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    // Banks the camera about its look_at axis, the look direction stays as it is.
+    // Roll is tracked in angle_left_up_look_at().z(), like rotate_look_at does, but the rotation
+    // is about the local z axis so it stays a pure roll whatever the yaw and pitch are.
+    pub fn add_roll(&mut self, radians: f64) {
+        let angle = *self.angle_left_up_look_at();
+        *self.angle_left_up_look_at_mut() = Vec3d::new(angle.x(), angle.y(), angle.z() + radians);
 
-            let z1 = v_z1[0] + v_z1[1] + v_z1[2];
-            let z2 = v_z2[0] + v_z2[1] + v_z2[2];
+        self.transform(&Matrix4x4::rotation_around_vec(&Vec3d::new(0.0, 0.0, 1.0), radians));
+    }
 
-            return z1.total_cmp(&z2);
+    pub fn set_roll(&mut self, radians: f64) {
+        self.add_roll(radians - self.angle_left_up_look_at().z());
+    }
+
+    pub fn roll(&self) -> f64 {
+        self.angle_left_up_look_at().z()
+    }
+
+    // Starts shaking the camera: until duration seconds of update_shake have passed, projection uses
+    // a random offset of up to intensity (world units) and a small random rotation, both fading to
+    // zero. The stored position and transform are never touched. A running shake is replaced.
+    pub fn add_shake(&mut self, intensity: f64, duration: f64) {
+        self.shake = Some(Shake {
+            intensity,
+            duration,
+            elapsed: 0.0,
+            rng: StdRng::seed_from_u64(self.shake_seed),
+            position: Vec3d::new(0.0, 0.0, 0.0),
+            angle: Vec3d::new(0.0, 0.0, 0.0),
         });
+        self.update_shake(0.0);
+    }
+
+    // seed of the shake offsets, the same seed and update steps give the same shake
+    pub fn set_shake_seed(&mut self, seed: u64) {
+        self.shake_seed = seed;
+    }
+
+    // advances the shake by dt seconds and picks the next offset
+    pub fn update_shake(&mut self, dt: f64) {
+        let Some(shake) = &mut self.shake else {
+            return;
+        };
+
+        shake.elapsed += dt;
+        if shake.elapsed >= shake.duration {
+            self.shake = None;
+            return;
+        }
+
+        let amplitude = shake.intensity * (1.0 - shake.elapsed / shake.duration);
+        let mut offset = || {
+            Vec3d::new(
+                shake.rng.gen_range(-1.0..=1.0),
+                shake.rng.gen_range(-1.0..=1.0),
+                shake.rng.gen_range(-1.0..=1.0),
+            )
+        };
+        shake.position = offset() * amplitude;
+        shake.angle = offset() * (amplitude * SHAKE_ROTATION);
+    }
+
+    // model matrix the camera projects with: model() plus the current shake
+    pub fn effective_model(&self) -> Matrix4x4 {
+        self.shaken(self.position())
+    }
+
+    // camera transform placed at eye, with the shake applied
+    fn shaken(&self, eye: &Vec3d) -> Matrix4x4 {
+        match &self.shake {
+            Some(shake) => {
+                Matrix4x4::translation(&(eye + &shake.position))
+                    * self.transform_matrix().clone()
+                    * Matrix4x4::rotation(&shake.angle)
+            }
+            None => Matrix4x4::translation(eye) * self.transform_matrix().clone(),
+        }
+    }
+
+    // whether the camera position is inside the closed mesh (see Mesh::signed_distance)
+    pub fn is_inside(&self, mesh: &dyn Mesh) -> bool {
+        mesh.signed_distance(self.position()) < 0.0
+    }
+
+    // Approximate fraction of the viewport covered by the mesh's bounding sphere (Mesh::bounding_sphere):
+    // 0 when the sphere is outside the frustum, 1 when the camera is inside it.
+    // The sphere's screen ellipse is approximated by its bounding rectangle clipped to the viewport, times pi / 4.
+    pub fn screen_coverage(&self, mesh: &dyn Mesh) -> f64 {
+        if mesh.triangles().is_empty() {
+            return 0.0;
+        }
+        let (center, radius) = mesh.bounding_sphere();
+
+        let v = Matrix4x4::view(&self.effective_model());
+        let c = Vec3d::from_vec4d(&(&v * &center.make_point_4d()));
+        if c.abs() <= radius {
+            return 1.0;
+        }
+        if !self.frustum_contains_sphere(&center, radius) {
+            return 0.0;
+        }
+
+        // angular radius, in normalized device units along y and x
+        let tan_half_fov = (PI * self.fov * 0.5 / 180.0).tan();
+        let ry = radius / (c.sqr_abs() - radius * radius).sqrt() / tan_half_fov;
+        let rx = ry / self.aspect;
+        let depth = c.z().max(self.znear);
+        let (cx, cy) = (c.x() / depth / (tan_half_fov * self.aspect), c.y() / depth / tan_half_fov);
+
+        let overlap = |c: f64, r: f64| ((c + r).min(1.0) - (c - r).max(-1.0)).max(0.0);
+        (overlap(cx, rx) * overlap(cy, ry) / 4.0 * PI / 4.0).min(1.0)
+    }
+
+    // False only when the world space sphere is entirely behind one of the clip planes, so nothing inside
+    // it can be on screen. project skips meshes whose Mesh::bounding_sphere fails this.
+    pub fn frustum_contains_sphere(&self, center: &Vec3d, radius: f64) -> bool {
+        let v = Matrix4x4::view(&self.effective_model());
+        let c = Vec3d::from_vec4d(&(&v * &center.make_point_4d()));
+
+        self.clip_planes.iter().all(|plane| plane.distance(&c) >= -radius)
+    }
+
+    fn in_frustum(&self, mesh: &dyn Mesh) -> bool {
+        let (center, radius) = mesh.bounding_sphere();
+        self.frustum_contains_sphere(&center, radius)
+    }
+
+    // replaces the built-in ambient shading of projected triangles
+    pub fn set_shader(&mut self, shader: Shader) {
+        self.shader = Some(shader);
+    }
+
+    // Filled and wireframe triangles by default. rasterize skips the fill in Wireframe mode and the
+    // edges (still sized by set_wireframe) in Filled mode.
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    // Edges drawn by rasterize, width in pixels (0 turns them off). With hidden_line
+    // the edges are depth tested against the filled triangles, so hidden edges are not drawn.
+    pub fn set_wireframe(&mut self, width: f64, hidden_line: bool) {
+        self.wireframe_width = width;
+        self.hidden_line = hidden_line;
+    }
+
+    // Skips triangles facing away from the eye, on by default. A triangle faces the eye when its normal,
+    // (p1 - p0) x (p2 - p0), points back at it: normal . (p0 - eye) < 0. Closed meshes need outward normals
+    // (Mesh::make_winding_consistent), turn it off for double-sided or transparent meshes.
+    pub fn set_cull_backfaces(&mut self, cull_backfaces: bool) {
+        self.cull_backfaces = cull_backfaces;
+    }
+
+    // background of rasterize / render_to_image, transparent black by default
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.clear_color = color;
+    }
+
+    // Depth every pixel starts at, nothing further away is drawn. Infinite by default,
+    // 1.0 (the far plane after the perspective divide) gives the same result for unclipped geometry.
+    pub fn set_clear_depth(&mut self, depth: f64) {
+        self.clear_depth = depth;
+    }
+
+    // Rounds projected x and y to integers for crisp pixel art. Integers are pixel corners:
+    // rasterize samples pixel centers at +0.5, so snapped edges run exactly between pixels.
+    // Normalized device coordinates (project_ndc) are never snapped.
+    pub fn set_pixel_snap(&mut self, pixel_snap: bool) {
+        self.pixel_snap = pixel_snap;
+    }
+
+    // texture coordinate interpolation in rasterize: perspective correct (default) or affine
+    pub fn set_perspective_correct(&mut self, perspective_correct: bool) {
+        self.perspective_correct = perspective_correct;
+    }
+
+    // Interlaced rasterizing for heavy scenes: each rasterize call only redraws every lines-th row,
+    // starting one row lower than the previous call, on top of the previous image, so the image
+    // is complete after lines calls. 0 or 1 draws every row.
+    pub fn set_progressive(&mut self, lines: u32) {
+        self.progressive = lines;
+        self.progressive_pass.set(0);
+        *self.progressive_frame.get_mut() = None;
+    }
+
+    // ordered dithering of render_to_image output
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+    }
+
+    // Tone mapping of the shaded triangle colors, so lighting summing above 1 doesn't clip to white.
+    // Colors are multiplied by exposure first. ToneMap::None with exposure 1 only clips them to 1.
+    pub fn set_tone_mapping(&mut self, tone_map: ToneMap, exposure: f64) {
+        self.tone_map = tone_map;
+        self.exposure = exposure;
+    }
+
+    // Retro look: every shaded color channel is reduced to levels values (2 gives pure 0 or 1),
+    // after tone mapping. 0 and 255 turn it off.
+    pub fn set_posterize(&mut self, levels: u8) {
+        self.posterize = levels;
+    }
+
+    // Lambertian shading by these lights (per face, one sided) on top of LIGHT_AMBIENT, each channel
+    // clamped to [0, 1]. Without lights triangles get the view dependent ambient shading. A shader replaces both.
+    pub fn set_lights(&mut self, lights: Vec<DirectionalLight>) {
+        self.lights = lights;
+    }
+
+    // environment reflected by meshes with reflective materials
+    pub fn set_environment(&mut self, cubemap: CubeMap) {
+        self.environment = Some(cubemap);
+    }
+
+    // Painter's order: opaque triangles near to far, then the transparent ones far to near so they blend
+    // over everything behind them (see paint_cmp).
+    pub fn sorted(&mut self) -> &Vec<Triangle> {
+        self.sort_by(paint_cmp)
+    }
+
+    // stable sort of the projected triangles with a custom comparator, sorted uses render priority and depth
+    pub fn sort_by(&mut self, cmp: impl FnMut(&Triangle, &Triangle) -> Ordering) -> &Vec<Triangle> {
+        let timer = Timer::start();
+        self.triangles.sort_by(cmp);
+        self.profiler.stop(Stage::Sort, timer);
+        self.profiler.stop(Stage::Total, timer);
         &self.triangles
     }
 
+    // Same order as sorted, but cheap when the buffer is already nearly sorted
+    // (e.g. re-sorting a kept buffer after a small camera move): an insertion sort
+    // that gives up and falls back to a full sort when too much has to be moved.
+    pub fn sorted_incremental(&mut self) -> &Vec<Triangle> {
+        let timer = Timer::start();
+        if !insertion_sort_by(&mut self.triangles, paint_cmp) {
+            self.triangles.sort_by(paint_cmp);
+        }
+        self.profiler.stop(Stage::Sort, timer);
+        self.profiler.stop(Stage::Total, timer);
+        &self.triangles
+    }
+
+    // Sorts the buffer and splits it into same-color runs. With group_colors triangles are also
+    // grouped by color (keeping depth order within each color), giving one batch per color;
+    // that breaks the painter's order between colors, so only use it when they don't overlap.
+    pub fn batches(&mut self, group_colors: bool) -> Vec<DrawBatch<'_>> {
+        let timer = Timer::start();
+        self.triangles.sort_by(paint_cmp);
+        if group_colors {
+            self.triangles.sort_by(|a, b| color_cmp(&a.color(), &b.color()));
+        }
+        self.profiler.stop(Stage::Sort, timer);
+        self.profiler.stop(Stage::Total, timer);
+
+        self.triangles
+            .chunk_by(|a, b| a.color() == b.color())
+            .map(|run| DrawBatch {
+                color: run[0].color(),
+                triangles: run,
+            })
+            .collect()
+    }
+
     pub fn project(&mut self, mesh: Rc<RefCell<dyn Mesh>>) -> Vec<Triangle> {
         let mesh = mesh.borrow();
 
-        if !mesh.visible() {
+        if !mesh.visible() || !self.in_frustum(&*mesh) {
             return vec![];
         }
 
-        let m = mesh.model();
-        let v = self.inv_model();
+        let mut triangles = std::mem::take(&mut self.triangles);
+        self.project_to(&*mesh, &mut triangles);
+        self.triangles = triangles;
+
+        self.triangles.clone()
+    }
 
-        let mut clipped_triangles: Vec<Triangle> = vec![];
-        let mut temp_buffer: Vec<Triangle> = vec![];
+    // Same as project, but appends the projected triangles to out instead of the camera's own buffer,
+    // so the camera is left untouched and several meshes can be projected independently.
+    pub fn project_to(&self, mesh: &dyn Mesh, out: &mut Vec<Triangle>) {
+        if !mesh.visible() || !self.in_frustum(mesh) {
+            return;
+        }
 
-        for t in mesh.triangles() {
-            let m_tri = t * &m;
+        let timer = Timer::start();
+        let start = out.len();
+        self.project_into(mesh, self.position(), &self.sp, &self.clip_planes, &self.scissor_planes, out);
+        self.snap_to_pixels(&mut out[start..]);
+        self.profiler.stop(Stage::Total, timer);
+    }
 
-            let dot = m_tri
-                .normal()
-                .dot(&(&Vec3d::from_vec4d(&m_tri.points()[0]) - self.position()).normalized());
-            // TODO: enable it
-            // if dot >= 0.0 {
-            //     continue;
-            // }
+    // Follows a world space point through the same pipeline as project, stage by stage.
+    // No clipping: points outside the frustum still get (meaningless) ndc and screen coordinates.
+    pub fn debug_project(&self, world: &Vec3d) -> ProjectionTrace {
+        let v = Matrix4x4::view(&self.effective_model());
+        let view = &v * &Vec4d::new(world.x(), world.y(), world.z(), 1.0);
+        let clip = &self.p * &view;
+        let ndc = clip / clip.w();
+        let screen = &Matrix4x4::screen_space(self.width, self.height) * &ndc;
 
-            let vm_tri = &m_tri * &v;
+        ProjectionTrace {
+            world: *world,
+            view,
+            clip,
+            ndc,
+            screen,
+        }
+    }
 
-            clipped_triangles.clear();
-            temp_buffer.clear();
+    // World space offset moving start_world by (screen_dx, screen_dy) pixels on screen, in the plane
+    // facing the camera at its depth: right and down on screen are -left and -up, and the same drag
+    // moves a farther point further. Points at or behind the eye give a zero offset.
+    pub fn screen_drag_to_world(&self, start_world: &Vec3d, screen_dx: f64, screen_dy: f64) -> Vec3d {
+        let w = self.debug_project(start_world).clip.w();
+        if w <= 0.0 {
+            return Vec3d::new(0.0, 0.0, 0.0);
+        }
+
+        // world units per pixel at this depth, the same along x and y
+        let scale = 2.0 * w * (PI * self.fov * 0.5 / 180.0).tan() / self.height as f64;
+        (self.left() * (-screen_dx * scale)) + (self.up() * (-screen_dy * scale))
+    }
+
+    // Screen space (min, max) corners of the visible part of the mesh, None if nothing of it is on screen.
+    pub fn screen_bounds(&self, mesh: &dyn Mesh) -> Option<(Vec2d, Vec2d)> {
+        let mut projected = vec![];
+        self.project_to(mesh, &mut projected);
+
+        let mut points = projected.iter().flat_map(|t| t.points().iter());
+        let first = points.next()?;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (first.x(), first.y(), first.x(), first.y());
+        for p in points {
+            min_x = min_x.min(p.x());
+            min_y = min_y.min(p.y());
+            max_x = max_x.max(p.x());
+            max_y = max_y.max(p.y());
+        }
+
+        Some((Vec2d::new(min_x, min_y), Vec2d::new(max_x, max_y)))
+    }
+
+    // Projects the mesh only up to normalized device coordinates (x, y in [-1, 1], z in [0, 1]),
+    // without mapping to the screen. Scissor is not applied as it is defined in screen space.
+    pub fn project_ndc(&self, mesh: Rc<RefCell<dyn Mesh>>) -> Vec<Triangle> {
+        let mesh = mesh.borrow();
+        let mut ndc = vec![];
+
+        if mesh.visible() {
+            self.project_into(&*mesh, self.position(), &self.p, &self.clip_planes, &[], &mut ndc);
+        }
+
+        ndc
+    }
+
+    // Renders the mesh twice, from two eyes shifted by ipd / 2 along the camera's left axis.
+    // Each eye gets a half-width viewport: the left eye is drawn into the left half of the screen
+    // and the right eye into the right half, so both buffers can be drawn as they are.
+    // Camera's own triangle buffer is not touched.
+    pub fn project_stereo(&self, mesh: Rc<RefCell<dyn Mesh>>, ipd: f64) -> (Vec<Triangle>, Vec<Triangle>) {
+        let mesh = mesh.borrow();
 
-            clipped_triangles.push(vm_tri);
+        let mut left = vec![];
+        let mut right = vec![];
 
-            for plane in &self.clip_planes {
-                while !clipped_triangles.is_empty() {
-                    let clip_result = plane.clip(clipped_triangles.last().unwrap());
-                    clipped_triangles.pop();
-                    for i in clip_result {
-                        temp_buffer.push(i);
+        if !mesh.visible() {
+            return (left, right);
+        }
+
+        let half_width = self.width / 2;
+        let aspect = half_width as f64 / self.height as f64;
+        let p = Matrix4x4::projection(self.fov, aspect, self.znear, self.zfar);
+        let left_sp = Matrix4x4::screen_space(half_width, self.height) * p.clone();
+        let right_sp = Matrix4x4::translation(&Vec3d::new(half_width as f64, 0.0, 0.0))
+            * Matrix4x4::screen_space(half_width, self.height)
+            * p;
+        let planes = Self::frustum_planes(self.fov, aspect, self.znear, self.zfar);
+
+        let offset = self.left() * (ipd / 2.0);
+        let left_eye = self.position() + &offset;
+        let right_eye = self.position() - &offset;
+
+        self.project_into(&*mesh, &left_eye, &left_sp, &planes, &self.scissor_planes, &mut left);
+        self.project_into(&*mesh, &right_eye, &right_sp, &planes, &self.scissor_planes, &mut right);
+        self.snap_to_pixels(&mut left);
+        self.snap_to_pixels(&mut right);
+
+        (left, right)
+    }
+
+    // rounds screen space x and y to whole pixels if pixel snapping is on
+    fn snap_to_pixels(&self, triangles: &mut [Triangle]) {
+        if !self.pixel_snap {
+            return;
+        }
+
+        let snap = |p: &Vec4d| Vec4d::new(p.x().round(), p.y().round(), p.z(), p.w());
+        for tri in triangles.iter_mut() {
+            let p = tri.points();
+            *tri = tri.with_points(snap(&p[0]), snap(&p[1]), snap(&p[2]));
+        }
+    }
+
+    // linear lighting of base_color: set_lights over LIGHT_AMBIENT, else 0.7 ambient plus 0.3 * |dot| (view cosine)
+    fn shade(&self, base_color: Color, normal: &Vec3d, dot: f64) -> Color {
+        if self.lights.is_empty() {
+            Color::from_rgba(
+                ((base_color.r * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
+                ((base_color.g * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
+                ((base_color.b * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
+                (base_color.a * 255.0) as u8,
+            )
+        } else {
+            let mut light = [LIGHT_AMBIENT; 3];
+            for l in self.lights.iter() {
+                let [r, g, b] = l.irradiance(normal);
+                light = [light[0] + r, light[1] + g, light[2] + b];
+            }
+            // not clamped, bright lights sum above 1 for the tone mapping
+            let [r, g, b] = light.map(|l| l.max(0.0) as f32);
+            Color::new(base_color.r * r, base_color.g * g, base_color.b * b, base_color.a)
+        }
+    }
+
+    // tone mapping, clipping to [0, 1] and posterizing of a lit color
+    fn finish_color(&self, color: Color) -> Color {
+        let color = self.tone_map.apply(color, self.exposure);
+        let color = Color::new(color.r.clamp(0.0, 1.0), color.g.clamp(0.0, 1.0), color.b.clamp(0.0, 1.0), color.a);
+        if self.posterize != u8::MAX {
+            posterize(color, self.posterize)
+        } else {
+            color
+        }
+    }
+
+    // transforms, clips and projects mesh triangles as seen from the eye point
+    // (with the camera's orientation) and appends the result to out
+    fn project_into(
+        &self,
+        mesh: &dyn Mesh,
+        eye: &Vec3d,
+        sp: &Matrix4x4,
+        clip_planes: &[Plane],
+        scissor_planes: &[Plane],
+        out: &mut Vec<Triangle>,
+    ) {
+        let m = TriangleTransform::new(&mesh.model());
+        let pipeline = ClipPipeline {
+            view: Matrix4x4::view(&self.shaken(eye)),
+            sp,
+            clip_planes,
+            scissor_planes,
+        };
+        #[cfg(not(feature = "parallel"))]
+        let mut workspace = self.clip_workspace.borrow_mut();
+        #[cfg(feature = "parallel")]
+        let mut lit_triangles = vec![];
+
+        // one pass per listed group, then one for the rest
+        let order = mesh.group_order();
+        let in_pass = |pass: usize, t: &Triangle| match order.get(pass) {
+            Some(&group) => t.group() == group,
+            None => !order.contains(&t.group()),
+        };
+        let triangles = (0..=order.len()).flat_map(|pass| mesh.triangles().iter().filter(move |t| in_pass(pass, t)));
+
+        // culling and shading need the mesh and the shader, they stay on this thread in order
+        for t in triangles {
+            let mut color = mesh.material_color(t);
+            let timer = Timer::start();
+            let mut m_tri = t.transform_by(&m);
+            self.profiler.stop(Stage::Transform, timer);
+
+            let view_dir = (&Vec3d::from_vec4d(&m_tri.points()[0]) - eye).normalized();
+            let dot = m_tri.normal().dot(&view_dir);
+
+            // per triangle reflection: view vector mirrored about the face normal
+            if let (Some(environment), Some(material)) = (&self.environment, mesh.material(t)) {
+                if material.reflectivity() > 0.0 {
+                    let normal = m_tri.normal();
+                    let reflected = view_dir - (normal * (2.0 * dot));
+                    color = lerp_color(color, environment.sample(&reflected), material.reflectivity() as f32);
+                }
+            }
+            if self.cull_backfaces && dot >= 0.0 {
+                continue;
+            }
+
+            // clipping keeps the color and texture of the triangle it cuts
+            match (&self.shader, m_tri.vertex_normals()) {
+                (Some(shader), _) => {
+                    let input = ShaderInput {
+                        normal: m_tri.normal(),
+                        centroid: m_tri.position(),
+                        base_color: color,
+                    };
+                    *m_tri.color_mut() = self.finish_color(shader(&input));
+                    *m_tri.vertex_colors_mut() = None;
+                }
+                // Gouraud shading: each vertex lit with its own normal, the colors get interpolated.
+                // Vertex colors stay linear, the rasterizer finishes them per pixel; the flat color is finished.
+                (None, Some(normals)) => {
+                    let colors = std::array::from_fn(|i| {
+                        let base = m_tri.vertex_colors().map_or(color, |colors| colors[i]);
+                        let view_dir = (Vec3d::from_vec4d(&m_tri.points()[i]) - *eye).normalized();
+                        self.shade(base, &normals[i], normals[i].dot(&view_dir))
+                    });
+                    m_tri.set_shaded(colors);
+                    *m_tri.color_mut() = self.finish_color(m_tri.color());
+                }
+                // flat shading, vertex colors are all lit with the face normal
+                (None, None) => match m_tri.vertex_colors().copied() {
+                    Some(colors) => {
+                        let normal = m_tri.normal();
+                        m_tri.set_shaded(colors.map(|c| self.shade(c, &normal, dot)));
+                        *m_tri.color_mut() = self.finish_color(m_tri.color());
                     }
+                    None => *m_tri.color_mut() = self.finish_color(self.shade(color, &m_tri.normal(), dot)),
+                },
+            }
+            if let Some(texture) = mesh.material(t).and_then(|material| material.texture()) {
+                *m_tri.texture_mut() = Some(texture.clone());
+            }
+
+            #[cfg(not(feature = "parallel"))]
+            pipeline.clip_triangle(&m_tri, &mut workspace, &self.profiler, out);
+            #[cfg(feature = "parallel")]
+            lit_triangles.push(m_tri);
+        }
+
+        // the rest is pure per triangle work, timed as a whole as clipping; order is kept
+        #[cfg(feature = "parallel")]
+        {
+            let timer = Timer::start();
+            out.par_extend(
+                lit_triangles
+                    .par_iter()
+                    .map_init(ClipWorkspace::default, |workspace, tri| {
+                        let mut res = vec![];
+                        pipeline.clip_triangle(tri, workspace, &Profiler::default(), &mut res);
+                        res
+                    })
+                    .flatten_iter(),
+            );
+            self.profiler.stop(Stage::Clip, timer);
+        }
+    }
+
+    // Rasterizes into width x height characters, one line per row, darkest to brightest from
+    // " .:-=+*#%@" by luminance times alpha, so the (transparent) background is blank.
+    // Terminal cells are about twice as tall as wide, a height of about half the width looks right.
+    pub fn render_ascii(&self, width: i32, height: i32) -> String {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+
+        self.render(width, height, |frame| {
+            let mut res = String::with_capacity((frame.width() + 1) * frame.height());
+            for row in frame.colors().chunks(frame.width().max(1)) {
+                for c in row {
+                    let luminance = ((0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b) * c.a).clamp(0.0, 1.0);
+                    res.push(RAMP[(luminance * (RAMP.len() - 1) as f32).round() as usize] as char);
                 }
-                std::mem::swap(&mut clipped_triangles, &mut temp_buffer);
+                res.push('\n');
             }
+            res
+        })
+    }
+
+    // SVG document of the projected triangles scaled to width x height, one polygon per triangle painted
+    // back to front in the order of sorted (the buffer itself is not reordered). The fill is the flat color
+    // with its alpha as fill-opacity; the render mode and set_wireframe decide fill and outline like in rasterize.
+    pub fn export_svg(&self, width: i32, height: i32) -> String {
+        let sx = width as f64 / self.width as f64;
+        let sy = height as f64 / self.height as f64;
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let stroke = if self.render_mode.draws_edges() && self.wireframe_width > 0.0 {
+            format!(r#" stroke="black" stroke-width="{}""#, self.wireframe_width)
+        } else {
+            String::new()
+        };
+
+        let mut order: Vec<&Triangle> = self.triangles.iter().collect();
+        order.sort_by(|a, b| paint_cmp(a, b));
+
+        let mut res = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}""#);
+        res.push_str(&format!(r#" viewBox="0 0 {width} {height}">"#));
+        res.push('\n');
+        for tri in order {
+            let points: Vec<String> =
+                tri.points().iter().map(|p| format!("{:.2},{:.2}", p.x() * sx, p.y() * sy)).collect();
+            let c = tri.color();
+            let fill = if self.render_mode.fills() {
+                let opacity = c.a.clamp(0.0, 1.0);
+                format!(r#"fill="rgb({},{},{})" fill-opacity="{opacity}""#, channel(c.r), channel(c.g), channel(c.b))
+            } else {
+                r#"fill="none""#.to_string()
+            };
+            res.push_str(&format!(r#"  <polygon points="{}" {}{}/>"#, points.join(" "), fill, stroke));
+            res.push('\n');
+        }
+        res.push_str("</svg>\n");
+
+        res
+    }
+
+    // Draws the projected triangle buffer into a width x height image with a depth buffer,
+    // followed by the wireframe edges if enabled. Pixels are row major, top row first.
+    // The depth test is per pixel, so the buffer needs no sorting and interpenetrating triangles come
+    // out right, unlike the painter's order of sorted. The cost is a CPU pass over every covered pixel
+    // plus a width x height depth buffer per call, where sorted only touches each triangle once and
+    // leaves the filling to the GPU (main.rs); transparent triangles still need sorted back to front.
+    pub fn rasterize(&self, width: i32, height: i32) -> Vec<Color> {
+        self.render(width, height, |frame| frame.colors().clone())
+    }
+
+    // rasterize quantized to 8 bit RGBA
+    pub fn render_to_image(&self, width: i32, height: i32) -> Vec<[u8; 4]> {
+        self.render(width, height, |frame| frame.to_rgba8(self.dither))
+    }
+
+    // Rasterizes into a frame buffer and hands it to output. Progressive rendering draws over the kept
+    // previous frame in place, otherwise every call starts from a fresh buffer.
+    fn render<T>(&self, width: i32, height: i32, output: impl FnOnce(&FrameBuffer) -> T) -> T {
+        let timer = Timer::start();
+        let (w, h) = (width.max(0) as usize, height.max(0) as usize);
+        let lines = self.progressive.max(1);
 
-            for clipped in clipped_triangles.iter() {
-                let color = clipped.color();
-                let ambient_color = Color::from_rgba(
-                    ((color.r * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
-                    ((color.g * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
-                    ((color.b * (0.3 * dot.abs() + 0.7) as f32) * 255.0) as u8,
-                    (color.a * 255.0) as u8,
-                );
-                let clipped_projected = clipped * &self.sp;
+        let res = if lines > 1 {
+            let mut kept = self.progressive_frame.borrow_mut();
+            let frame = match kept.take() {
+                Some(frame) if frame.width() == w && frame.height() == h => kept.insert(frame),
+                _ => kept.insert(FrameBuffer::new(w, h, self.clear_color, self.clear_depth)),
+            };
+            let pass = self.progressive_pass.get();
+            self.progressive_pass.set((pass + 1) % lines);
+            frame.set_interlace(lines as usize, pass as usize);
+            frame.clear_rows(self.clear_color, self.clear_depth);
+            self.draw_into(frame, width, height);
+            output(frame)
+        } else {
+            let mut frame = FrameBuffer::new(w, h, self.clear_color, self.clear_depth);
+            self.draw_into(&mut frame, width, height);
+            output(&frame)
+        };
+
+        self.profiler.stop(Stage::Rasterize, timer);
+        self.profiler.stop(Stage::Total, timer);
+        res
+    }
 
-                let cp_points = clipped_projected.points();
-                let clip_proj_norm = Triangle::new(
-                    // ambient_color,
-                    ambient_color,
-                    cp_points[0].clone() / cp_points[0].w(),
-                    cp_points[1].clone() / cp_points[1].w(),
-                    cp_points[2].clone() / cp_points[2].w(),
-                );
-                self.triangles.push(clip_proj_norm);
+    fn draw_into(&self, frame: &mut FrameBuffer, width: i32, height: i32) {
+        let sx = width as f64 / self.width as f64;
+        let sy = height as f64 / self.height as f64;
+        // the rasterizer wants 1 / w before the divide (the view space depth), recovered from z
+        let inv_depth = |z: f64| (self.zfar - z * (self.zfar - self.znear)) / (self.zfar * self.znear);
+        let to_frame =
+            |tri: &Triangle| (*tri.points()).map(|p| Vec4d::new(p.x() * sx, p.y() * sy, p.z(), inv_depth(p.z())));
+
+        for tri in self.triangles.iter().filter(|_| self.render_mode.fills()) {
+            match (tri.uvs(), tri.texture()) {
+                (Some(uvs), Some(_)) => frame.fill_textured(
+                    &to_frame(tri),
+                    uvs,
+                    |u, v| tri.sample_texture(u, v).unwrap_or(WHITE),
+                    tri.color(),
+                    self.perspective_correct,
+                ),
+                _ => match tri.vertex_colors() {
+                    Some(colors) => {
+                        frame.fill_shaded(&to_frame(tri), colors, |c| self.finish_color(c), self.perspective_correct)
+                    }
+                    None => frame.fill_triangle(&to_frame(tri), tri.color()),
+                },
             }
         }
 
-        return self.triangles.clone();
+        if self.render_mode.draws_edges() && self.wireframe_width > 0.0 {
+            for tri in self.triangles.iter() {
+                let p = to_frame(tri);
+                for i in 0..3 {
+                    frame.draw_line(&p[i], &p[(i + 1) % 3], self.wireframe_width, BLACK, self.hidden_line);
+                }
+            }
+        }
+    }
+
+    // Time spent per pipeline stage since the last reset_profile. Total covers the project, sort
+    // and rasterize calls as a whole, the other stages are the timed parts inside of them.
+    #[cfg(feature = "profiling")]
+    pub fn profile(&self) -> Profile {
+        self.profiler.get()
     }
 
-    fn buffsize(&self) -> usize {
+    #[cfg(feature = "profiling")]
+    pub fn reset_profile(&self) {
+        self.profiler.reset();
+    }
+
+    pub fn buffsize(&self) -> usize {
         self.triangles.len()
     }
 
@@ -154,6 +1059,144 @@ impl MyCamera {
     }
 }
 
+// draw order: by render priority, then by depth
+fn depth_cmp(t1: &Triangle, t2: &Triangle) -> Ordering {
+    let priority = t1.render_priority().cmp(&t2.render_priority());
+    if priority.is_ne() {
+        return priority;
+    }
+
+    let mut v_z1 = [t1.points()[0].z(), t1.points()[1].z(), t1.points()[2].z()];
+    let mut v_z2 = [t2.points()[0].z(), t2.points()[1].z(), t2.points()[2].z()];
+
+    v_z1.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    v_z2.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let z1 = v_z1[0] + v_z1[1] + v_z1[2];
+    let z2 = v_z2[0] + v_z2[1] + v_z2[2];
+
+    z1.total_cmp(&z2)
+}
+
+// depth_cmp, except that transparent triangles (alpha below 1) go after the opaque ones of the same render
+// priority and are ordered far to near among themselves, as alpha blending needs.
+fn paint_cmp(t1: &Triangle, t2: &Triangle) -> Ordering {
+    let priority = t1.render_priority().cmp(&t2.render_priority());
+    if priority.is_ne() {
+        return priority;
+    }
+
+    let transparent1 = t1.color().a < 1.0;
+    let transparent2 = t2.color().a < 1.0;
+    match (transparent1, transparent2) {
+        (false, false) => depth_cmp(t1, t2),
+        (true, true) => depth_cmp(t2, t1),
+        _ => transparent1.cmp(&transparent2),
+    }
+}
+
+fn color_cmp(c1: &Color, c2: &Color) -> Ordering {
+    [c1.r, c1.g, c1.b, c1.a]
+        .iter()
+        .zip([c2.r, c2.g, c2.b, c2.a].iter())
+        .map(|(a, b)| a.total_cmp(b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+// Stable insertion sort, n - 1 comparisons for a sorted slice. Stops and returns false
+// once the number of moves exceeds a few per element, meaning the slice was far from sorted.
+fn insertion_sort_by<T>(v: &mut [T], mut cmp: impl FnMut(&T, &T) -> Ordering) -> bool {
+    let mut budget = 4 * v.len();
+
+    for i in 1..v.len() {
+        let mut j = i;
+        while j > 0 && cmp(&v[j - 1], &v[j]) == Ordering::Greater {
+            if budget == 0 {
+                return false;
+            }
+            budget -= 1;
+            v.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+
+    true
+}
+
+// camera space to screen space part of the projection, the same for every triangle of a mesh
+struct ClipPipeline<'a> {
+    view: Matrix4x4,
+    sp: &'a Matrix4x4,
+    clip_planes: &'a [Plane],
+    scissor_planes: &'a [Plane],
+}
+
+impl ClipPipeline<'_> {
+    // View transform, frustum clipping, projection with the perspective divide and scissoring
+    // of one world space triangle, the results are appended to out.
+    fn clip_triangle(
+        &self,
+        tri: &Triangle,
+        workspace: &mut ClipWorkspace,
+        profiler: &Profiler,
+        out: &mut Vec<Triangle>,
+    ) {
+        let ClipWorkspace {
+            clipped: clipped_triangles,
+            temp: temp_buffer,
+            scissored: scissored_triangles,
+        } = workspace;
+
+        let timer = Timer::start();
+        let vm_tri = tri * &self.view;
+        profiler.stop(Stage::Transform, timer);
+
+        clipped_triangles.clear();
+        temp_buffer.clear();
+
+        clipped_triangles.push(vm_tri);
+
+        let timer = Timer::start();
+        Self::clip(self.clip_planes, clipped_triangles, temp_buffer);
+        profiler.stop(Stage::Clip, timer);
+
+        for clipped in clipped_triangles.iter() {
+            let timer = Timer::start();
+            let clipped_projected = clipped * self.sp;
+            let cp_points = clipped_projected.points();
+            let clip_proj_norm = clipped_projected.with_points(
+                cp_points[0] / cp_points[0].w(),
+                cp_points[1] / cp_points[1].w(),
+                cp_points[2] / cp_points[2].w(),
+            );
+            profiler.stop(Stage::Transform, timer);
+
+            if self.scissor_planes.is_empty() {
+                out.push(clip_proj_norm);
+            } else {
+                scissored_triangles.clear();
+                scissored_triangles.push(clip_proj_norm);
+                let timer = Timer::start();
+                Self::clip(self.scissor_planes, scissored_triangles, temp_buffer);
+                profiler.stop(Stage::Clip, timer);
+                out.append(scissored_triangles);
+            }
+        }
+    }
+
+    // clips triangles against every plane in turn, temp is a scratch buffer
+    fn clip(planes: &[Plane], triangles: &mut Vec<Triangle>, temp: &mut Vec<Triangle>) {
+        for plane in planes {
+            temp.clear();
+            while let Some(tri) = triangles.pop() {
+                plane.clip_into(&tri, temp);
+            }
+            std::mem::swap(triangles, temp);
+        }
+    }
+}
+
 impl Object for MyCamera {
     fn nametag(&self) -> &ObjectNameTag {
         &self.obj.nametag
@@ -192,3 +1235,1140 @@ impl Object for MyCamera {
         &mut self.obj.attached_objects
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::f64::consts::PI;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use macroquad::prelude::{Color, BLANK, BLUE, RED, WHITE};
+
+    use crate::light::DirectionalLight;
+    use crate::material::{CubeMap, Material, Texture};
+    use crate::math::matrix4x4::Matrix4x4;
+    use crate::math::vec_2d::Vec2d;
+    use crate::math::vec_4d::Vec4d;
+    use crate::math::vec_3d::Vec3d;
+    use crate::object::{Cube, LoadedMesh, Mesh, Object, ObjectNameTag};
+    use crate::raster::ToneMap;
+    use crate::triangle::Triangle;
+
+    use super::{depth_cmp, insertion_sort_by, paint_cmp, MyCamera, RenderMode, LIGHT_AMBIENT};
+
+    fn camera() -> MyCamera {
+        let mut camera = MyCamera::new("Camera");
+        camera.init(800, 600, 90.0, 0.1, 100.0);
+        camera
+    }
+
+    fn centroid_x(tris: &[Triangle]) -> f64 {
+        tris.iter().map(|t| t.position().x()).sum::<f64>() / tris.len() as f64
+    }
+
+    #[test]
+    fn project_material_groups() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        *cube.materials_mut() = vec![
+            Material::new(Color::new(1.0, 0.0, 0.0, 1.0)),
+            Material::new(Color::new(0.0, 0.0, 1.0, 1.0)),
+        ];
+        for (i, tri) in cube.triangles_mut().iter_mut().enumerate() {
+            *tri.material_id_mut() = Some(if i < 6 { 0 } else { 1 });
+        }
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        let tris = camera.project(Rc::new(RefCell::new(cube)));
+
+        assert_eq!(tris.len(), 12);
+        for (i, tri) in tris.iter().enumerate() {
+            let c = tri.color();
+            assert_eq!(c.g, 0.0);
+            if i < 6 {
+                assert!(c.r > 0.0 && c.b == 0.0);
+            } else {
+                assert!(c.r == 0.0 && c.b > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn project_stereo_eyes() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+
+        let camera = camera();
+        let (left, right) = camera.project_stereo(Rc::new(RefCell::new(cube)), 0.5);
+
+        assert!(!left.is_empty() && !right.is_empty());
+        // left viewport is [0, 400), right viewport is [400, 800)
+        let left_x = centroid_x(&left);
+        let right_x = centroid_x(&right);
+        assert!(left_x > 200.0 && left_x < 400.0);
+        assert!(right_x > 400.0 && right_x < 600.0);
+        assert!(camera.buffsize() == 0);
+
+        // a tiny cube straight ahead: the left eye sees it right of its viewport center and the right eye
+        // left of it, each off by ipd / 2 * p[0][0] / w (w = 5 + 1) in ndc, which is 1.5 / 24 * 200 px
+        let mut dot = Cube::new(ObjectNameTag::new("Dot"), 0.01, WHITE);
+        dot.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let (left, right) = camera.project_stereo(Rc::new(RefCell::new(dot)), 0.5);
+        assert!((centroid_x(&left) - 212.5).abs() < 0.5);
+        assert!((centroid_x(&right) - 587.5).abs() < 0.5);
+    }
+
+    #[test]
+    fn scissor_clips_projected_triangles() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        let mut camera = camera();
+        let full = camera.project(cube.clone());
+        camera.clear();
+
+        // top part of the screen down to its center, the cube straddles the bottom vertex
+        let scissor = [Vec2d::new(0.0, 0.0), Vec2d::new(800.0, 0.0), Vec2d::new(400.0, 300.0)];
+        camera.set_scissor(scissor.to_vec());
+        let scissored = camera.project(cube.clone());
+        camera.clear();
+
+        assert!(!scissored.is_empty());
+        assert!(scissored.iter().any(|t| full.iter().all(|f| f.points() != t.points())));
+        for tri in &scissored {
+            for p in tri.points() {
+                // below both slanted edges and the top one
+                assert!(p.y() >= -1e-6);
+                assert!(p.y() <= p.x() * 0.75 + 1e-6);
+                assert!(p.y() <= (800.0 - p.x()) * 0.75 + 1e-6);
+            }
+        }
+
+        // far away from the cube
+        camera.set_scissor(vec![Vec2d::new(0.0, 0.0), Vec2d::new(100.0, 0.0), Vec2d::new(0.0, 100.0)]);
+        assert!(camera.project(cube).is_empty());
+    }
+
+    #[test]
+    fn backface_culling() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let mut camera = camera();
+
+        // straight on only the near -Z face points back at the eye
+        let mut out = vec![];
+        camera.project_to(&cube, &mut out);
+        assert_eq!(out.len(), 2);
+
+        // from a corner three faces do
+        camera.translate(&Vec3d::new(3.0, 3.0, 0.0));
+        out.clear();
+        camera.project_to(&cube, &mut out);
+        assert_eq!(out.len(), 6);
+
+        camera.set_cull_backfaces(false);
+        out.clear();
+        camera.project_to(&cube, &mut out);
+        assert_eq!(out.len(), 12);
+    }
+
+    #[test]
+    fn project_to_keeps_camera_state() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        let mut out = vec![];
+        camera.project_to(&cube, &mut out);
+        let once = out.len();
+        camera.project_to(&cube, &mut out);
+
+        assert_eq!(once, 12);
+        assert_eq!(out.len(), 2 * once);
+        assert_eq!(camera.buffsize(), 0);
+    }
+
+    #[test]
+    fn reflective_floor_samples_environment() {
+        let black = Color::new(0.0, 0.0, 0.0, 1.0);
+        let mut floor = Cube::new(ObjectNameTag::new("Floor"), 1.0, WHITE);
+        let mut tri = Triangle::new(
+            WHITE,
+            Vec4d::new(-1.0, -5.0, 0.0, 1.0),
+            Vec4d::new(0.0, -5.0, 2.0, 1.0),
+            Vec4d::new(1.0, -5.0, 0.0, 1.0),
+        );
+        assert_eq!(tri.normal(), Vec3d::new(0.0, 1.0, 0.0));
+        *tri.material_id_mut() = Some(0);
+        *floor.triangles_mut() = vec![tri];
+        let mut mirror = Material::new(black);
+        *mirror.reflectivity_mut() = 1.0;
+        *floor.materials_mut() = vec![mirror];
+
+        let mut camera = camera();
+        // look down
+        camera.rotate(&Vec3d::new(PI / 2.0, 0.0, 0.0));
+        let green = Color::new(0.0, 1.0, 0.0, 1.0);
+        camera.set_environment(CubeMap::new([black, black, green, black, black, black]));
+
+        let tris = camera.project(Rc::new(RefCell::new(floor)));
+        assert!(!tris.is_empty());
+        for tri in tris {
+            let c = tri.color();
+            assert!(c.r == 0.0 && c.g > 0.5 && c.b == 0.0);
+        }
+    }
+
+    #[test]
+    fn project_ndc_maps_to_screen() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        let mut camera = camera();
+        let ndc = camera.project_ndc(cube.clone());
+        let screen = camera.project(cube);
+
+        assert_eq!(ndc.len(), screen.len());
+        let s = Matrix4x4::screen_space(800, 600);
+        for (n, t) in ndc.iter().zip(&screen) {
+            for (np, tp) in n.points().iter().zip(t.points()) {
+                assert!(np.x().abs() <= 1.0 && np.y().abs() <= 1.0);
+                assert!(np.z() >= 0.0 && np.z() <= 1.0);
+                assert_eq!(s.clone() * *np, *tp);
+            }
+        }
+    }
+
+    #[test]
+    fn with_viewport_scales_output() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(1.0, 0.5, 5.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.translate(&Vec3d::new(0.0, 0.0, -1.0));
+        camera.rotate(&Vec3d::new(0.0, 0.1, 0.0));
+        camera.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 2.0, WHITE)]);
+        camera.set_tone_mapping(ToneMap::Reinhard, 1.5);
+        camera.set_posterize(4);
+
+        let small = camera.with_viewport(64, 64).project(cube.clone());
+        let large = camera.with_viewport(128, 128).project(cube.clone());
+        // lit, tone mapped and posterized like the original
+        let colors = |tris: &[Triangle]| tris.iter().map(|t| t.color()).collect::<Vec<Color>>();
+        assert_eq!(colors(&small), colors(&camera.project(cube)));
+        assert_eq!(colors(&large), colors(&small));
+        camera.clear();
+
+        assert_eq!(small.len(), large.len());
+        let centroid_y = |tris: &[Triangle]| tris.iter().map(|t| t.position().y()).sum::<f64>() / tris.len() as f64;
+        assert!((centroid_x(&small) / 64.0 - centroid_x(&large) / 128.0).abs() < 1e-9);
+        assert!((centroid_y(&small) / 64.0 - centroid_y(&large) / 128.0).abs() < 1e-9);
+        assert!((centroid_x(&small) - 32.0).abs() > 1.0);
+        assert_eq!(camera.buffsize(), 0);
+    }
+
+    #[test]
+    fn lerp_cameras() {
+        let mut a = camera();
+        a.translate(&Vec3d::new(1.0, 0.0, 0.0));
+        a.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 2.0, WHITE)]);
+        a.set_tone_mapping(ToneMap::Reinhard, 1.5);
+        a.set_posterize(4);
+        let mut b = MyCamera::new("Other");
+        b.init(800, 600, 60.0, 0.1, 100.0);
+        b.translate(&Vec3d::new(3.0, 2.0, -4.0));
+        b.rotate(&Vec3d::new(0.0, PI / 2.0, 0.0));
+
+        let start = a.lerp(&b, 0.0);
+        assert_eq!(start.model(), a.model());
+        assert_eq!(start.fov, 90.0);
+
+        let end = a.lerp(&b, 1.0);
+        assert_eq!(end.model(), b.model());
+        assert_eq!(end.fov, 60.0);
+
+        let mid = a.lerp(&b, 0.5);
+        assert_eq!(*mid.position(), Vec3d::new(2.0, 1.0, -2.0));
+        assert_eq!(mid.fov, 75.0);
+        assert_eq!(*mid.transform_matrix(), Matrix4x4::rotation(&Vec3d::new(0.0, PI / 4.0, 0.0)));
+        assert_eq!(mid.p, Matrix4x4::projection(75.0, 800.0 / 600.0, 0.1, 100.0));
+
+        // still lit and tone mapped mid cut
+        assert_eq!(mid.lights.len(), 1);
+        assert_eq!((mid.tone_map, mid.exposure, mid.posterize), (ToneMap::Reinhard, 1.5, 4));
+    }
+
+    #[test]
+    fn focus_point() {
+        let mut camera = camera();
+        assert_eq!(camera.focus_point(5.0), Vec3d::new(0.0, 0.0, 5.0));
+
+        camera.translate(&Vec3d::new(1.0, 2.0, 3.0));
+        camera.rotate(&Vec3d::new(0.0, PI / 2.0, 0.0));
+        assert_eq!(camera.focus_point(2.0), Vec3d::new(3.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn screen_coverage() {
+        let camera = camera();
+        let at = |z: f64, x: f64| {
+            let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+            cube.translate(&Vec3d::new(x, 0.0, z));
+            camera.screen_coverage(&cube)
+        };
+
+        let (near, far) = (at(3.0, 0.0), at(30.0, 0.0));
+        assert!(near > far && far > 0.0);
+        assert!(near < 1.0);
+        // a tenth of the distance covers about a hundred times the area
+        assert!(near / far > 50.0 && near / far < 150.0);
+        assert_eq!(at(-5.0, 0.0), 0.0);
+        assert_eq!(at(3.0, 50.0), 0.0);
+        assert_eq!(at(0.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn frustum_culling() {
+        let mut camera = camera();
+        assert!(camera.frustum_contains_sphere(&Vec3d::new(0.0, 0.0, 5.0), 1.0));
+        assert!(!camera.frustum_contains_sphere(&Vec3d::new(0.0, 0.0, -5.0), 1.0));
+        // off to the side, only a big enough sphere reaches into the 90 degree fov
+        assert!(!camera.frustum_contains_sphere(&Vec3d::new(20.0, 0.0, 5.0), 1.0));
+        assert!(camera.frustum_contains_sphere(&Vec3d::new(20.0, 0.0, 5.0), 12.0));
+
+        let cube_at = |z: f64| {
+            let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+            cube.translate(&Vec3d::new(0.0, 0.0, z));
+            Rc::new(RefCell::new(cube))
+        };
+        assert!(camera.project(cube_at(-5.0)).is_empty());
+        assert!(!camera.project(cube_at(5.0)).is_empty());
+    }
+
+    #[test]
+    fn group_order() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        // far +Z face first, then the sides, the unlisted near -Z face last
+        for (i, tri) in cube.triangles_mut().iter_mut().enumerate() {
+            *tri.group_mut() = match i {
+                0..=1 => 1,
+                4..=5 => 7,
+                _ => 3,
+            };
+        }
+        *cube.group_order_mut() = vec![7, 3];
+
+        let mut out = vec![];
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.project_to(&cube, &mut out);
+        let groups: Vec<u32> = out.iter().map(|t| t.group()).collect();
+        assert_eq!(groups, [7, 7, 3, 3, 3, 3, 3, 3, 3, 3, 1, 1]);
+        assert!(out[11].position().z() < out[0].position().z());
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn stage_timers() {
+        let mut camera = camera();
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        camera.reset_profile();
+
+        camera.project(Rc::new(RefCell::new(cube)));
+        camera.sorted();
+        camera.rasterize(64, 64);
+
+        let profile = camera.profile();
+        assert!(profile.transform_ns > 0);
+        assert!(profile.clip_ns > 0);
+        assert!(profile.sort_ns > 0);
+        assert!(profile.rasterize_ns > 0);
+        // the stages are nested in the total, which only adds the shading and bookkeeping between them
+        assert!(profile.stages_ns() <= profile.total_ns);
+        assert!(profile.stages_ns() * 2 >= profile.total_ns);
+
+        camera.reset_profile();
+        assert_eq!(camera.profile(), Default::default());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_projection_keeps_order() {
+        let mut sphere = crate::object::Sphere::new(ObjectNameTag::new("Sphere"), 2.0, 24, 32, WHITE);
+        sphere.translate(&Vec3d::new(0.5, 0.0, 3.0));
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+
+        let mut all = vec![];
+        camera.project_to(&sphere, &mut all);
+        // the same triangles one at a time, some cut by the side planes
+        let mut one_by_one = vec![];
+        for tri in sphere.triangles() {
+            let mut single = LoadedMesh::new(ObjectNameTag::new("Single"), vec![tri.clone()], WHITE);
+            single.translate(sphere.position());
+            camera.project_to(&single, &mut one_by_one);
+        }
+
+        assert!(all.len() > 1000);
+        assert_eq!(all.len(), one_by_one.len());
+        for (a, b) in all.iter().zip(one_by_one.iter()) {
+            assert_eq!(a.points(), b.points());
+            assert_eq!(a.color(), b.color());
+        }
+    }
+
+    #[test]
+    fn roll_about_look_axis() {
+        let mut camera = camera();
+        camera.rotate_up(0.4);
+        camera.rotate_left(-0.2);
+        let (look, up) = (camera.look_at(), camera.up());
+
+        camera.add_roll(PI / 6.0);
+        assert_eq!(camera.look_at(), look);
+        assert!((camera.up().dot(&up) - (PI / 6.0).cos()).abs() < 1e-12);
+        // turned about the look axis, in its plane
+        assert!(camera.up().cross(&up).normalized().dot(&look).abs() > 1.0 - 1e-12);
+
+        camera.set_roll(-PI / 4.0);
+        assert!((camera.roll() + PI / 4.0).abs() < 1e-12);
+        assert!((camera.up().dot(&up) - (PI / 4.0).cos()).abs() < 1e-12);
+        camera.set_roll(0.0);
+        assert_eq!(camera.up(), up);
+        assert_eq!(camera.look_at(), look);
+    }
+
+    #[test]
+    fn shake_wears_off() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let mut camera = camera();
+        camera.translate(&Vec3d::new(0.5, 0.0, 0.0));
+        camera.rotate(&Vec3d::new(0.0, 0.2, 0.0));
+        let base = camera.model();
+        let mut still = vec![];
+        camera.project_to(&cube, &mut still);
+
+        let shaken = |seed: u64| {
+            let mut camera = camera.with_viewport(800, 600);
+            camera.set_shake_seed(seed);
+            camera.add_shake(0.3, 0.5);
+            let mut models = vec![];
+            for _ in 0..4 {
+                camera.update_shake(0.1);
+                models.push(camera.effective_model());
+            }
+            models
+        };
+        assert!(shaken(7).iter().zip(shaken(7)).all(|(a, b)| *a == b));
+
+        camera.set_shake_seed(7);
+        camera.add_shake(0.3, 0.5);
+        camera.update_shake(0.1);
+        assert!(camera.effective_model() != base);
+        assert_eq!(camera.model(), base);
+        let mut moved = vec![];
+        camera.project_to(&cube, &mut moved);
+        assert!(moved.iter().zip(still.iter()).any(|(a, b)| a.points() != b.points()));
+
+        camera.update_shake(0.4);
+        assert_eq!(camera.effective_model(), base);
+        let mut after = vec![];
+        camera.project_to(&cube, &mut after);
+        assert!(after.iter().zip(still.iter()).all(|(a, b)| a.points() == b.points()));
+    }
+
+    #[test]
+    fn inside_mesh() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let mut camera = camera();
+        assert!(!camera.is_inside(&cube));
+
+        camera.translate(&Vec3d::new(0.5, -0.5, 4.5));
+        assert!(camera.is_inside(&cube));
+
+        camera.translate(&Vec3d::new(0.0, 0.0, 2.0));
+        assert!(!camera.is_inside(&cube));
+    }
+
+    #[test]
+    fn depth_buffer_resolves_interpenetration() {
+        // two triangles crossing along x = 0 with the same average depth: red is nearer on the left
+        // (negative x), blue on the right, no draw order gets both halves right
+        let (red, blue) = (Color::new(1.0, 0.0, 0.0, 1.0), Color::new(0.0, 0.0, 1.0, 1.0));
+        let tilted = |color: Color, slope: f64| {
+            let point = |x: f64, y: f64| Vec4d::new(x, y, 5.0 + slope * x, 1.0);
+            Triangle::new(color, point(-3.0, -3.0), point(3.0, -3.0), point(0.0, 3.0))
+        };
+        let mesh = LoadedMesh::new(ObjectNameTag::new("Cross"), vec![tilted(red, 0.5), tilted(blue, -0.5)], WHITE);
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.project(Rc::new(RefCell::new(mesh)));
+        let pixels = camera.rasterize(800, 600);
+        let at = |x: f64| {
+            let screen = camera.debug_project(&Vec3d::new(x, 0.0, 5.0)).screen;
+            pixels[screen.y() as usize * 800 + screen.x() as usize]
+        };
+
+        let (left, right) = (at(-1.0), at(1.0));
+        assert!(left.r > 0.5 && left.b == 0.0);
+        assert!(right.b > 0.5 && right.r == 0.0);
+    }
+
+    #[test]
+    fn render_priority_breaks_depth_ties() {
+        let glass = Color::new(0.2, 0.4, 1.0, 0.5);
+        let panel = |priority: i32| {
+            let mut tri = Triangle::new(
+                glass,
+                Vec4d::new(0.0, 0.0, 0.5, 1.0),
+                Vec4d::new(1.0, 0.0, 0.5, 1.0),
+                Vec4d::new(0.0, 1.0, 0.5, 1.0),
+            );
+            *tri.render_priority_mut() = priority;
+            tri
+        };
+
+        let mut camera = camera();
+        camera.triangles = vec![panel(2), panel(-1), panel(0)];
+        let sorted: Vec<i32> = camera.sorted().iter().map(|t| t.render_priority()).collect();
+        assert_eq!(sorted, vec![-1, 0, 2]);
+
+        camera.triangles = vec![panel(1), panel(0)];
+        assert_eq!(camera.sorted_incremental().last().unwrap().render_priority(), 1);
+    }
+
+    #[test]
+    fn custom_sort() {
+        let mut camera = camera();
+        camera.triangles = [3.0, -1.0, 7.0, 0.5]
+            .iter()
+            .map(|&x| {
+                Triangle::new(
+                    WHITE,
+                    Vec4d::new(x, 0.0, 10.0 - x, 1.0),
+                    Vec4d::new(x + 1.0, 0.0, 10.0 - x, 1.0),
+                    Vec4d::new(x, 1.0, 10.0 - x, 1.0),
+                )
+            })
+            .collect();
+
+        let sorted = camera.sort_by(|a, b| a.position().x().total_cmp(&b.position().x()));
+        let xs: Vec<f64> = sorted.iter().map(|t| t.points()[0].x()).collect();
+        assert_eq!(xs, vec![-1.0, 0.5, 3.0, 7.0]);
+
+        // depth order is the other way round here
+        let xs: Vec<f64> = camera.sorted().iter().map(|t| t.points()[0].x()).collect();
+        assert_eq!(xs, vec![7.0, 3.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn draw_batches() {
+        let mut camera = camera();
+        camera.triangles = (0..6)
+            .map(|i| {
+                let z = i as f64;
+                Triangle::new(
+                    if i % 2 == 0 { RED } else { BLUE },
+                    Vec4d::new(0.0, 0.0, z, 1.0),
+                    Vec4d::new(1.0, 0.0, z, 1.0),
+                    Vec4d::new(0.0, 1.0, z, 1.0),
+                )
+            })
+            .collect();
+
+        assert_eq!(camera.batches(false).len(), 6);
+
+        let batches = camera.batches(true);
+        assert_eq!(batches.len(), 2);
+        for batch in batches.iter() {
+            assert_eq!(batch.triangles.len(), 3);
+            assert!(batch.triangles.iter().all(|t| t.color() == batch.color));
+            assert!(batch.triangles.windows(2).all(|w| depth_cmp(&w[0], &w[1]).is_lt()));
+        }
+    }
+
+    #[test]
+    fn incremental_sort_of_sorted_buffer() {
+        let tris: Vec<Triangle> = (0..1000)
+            .map(|i| {
+                let z = ((i * 7919) % 1000) as f64;
+                Triangle::new(
+                    WHITE,
+                    Vec4d::new(0.0, 0.0, z, 1.0),
+                    Vec4d::new(1.0, 0.0, z, 1.0),
+                    Vec4d::new(0.0, 1.0, z, 1.0),
+                )
+            })
+            .collect();
+
+        let mut full = tris.clone();
+        let mut full_comparisons = 0;
+        full.sort_by(|a, b| {
+            full_comparisons += 1;
+            depth_cmp(a, b)
+        });
+
+        let mut incremental = full.clone();
+        let mut incremental_comparisons = 0;
+        assert!(insertion_sort_by(&mut incremental, |a, b| {
+            incremental_comparisons += 1;
+            depth_cmp(a, b)
+        }));
+
+        assert_eq!(incremental_comparisons, 999);
+        assert!(incremental_comparisons * 5 < full_comparisons);
+        assert!(incremental.iter().zip(&full).all(|(a, b)| a.points() == b.points()));
+
+        // far from sorted buffer falls back to the full sort
+        let mut camera = camera();
+        camera.triangles = tris;
+        let sorted = camera.sorted_incremental();
+        assert!(sorted.windows(2).all(|w| depth_cmp(&w[0], &w[1]) != std::cmp::Ordering::Greater));
+    }
+
+    #[test]
+    fn transparent_triangles_sorted_back_to_front() {
+        let mut glass = RED;
+        glass.a = 0.5;
+        let tri = |color: Color, z: f64| {
+            Triangle::new(
+                color,
+                Vec4d::new(0.0, 0.0, z, 1.0),
+                Vec4d::new(1.0, 0.0, z, 1.0),
+                Vec4d::new(0.0, 1.0, z, 1.0),
+            )
+        };
+        let mut camera = camera();
+        camera.triangles = vec![
+            tri(glass, 0.2),
+            tri(WHITE, 0.6),
+            tri(glass, 0.9),
+            tri(WHITE, 0.1),
+            tri(glass, 0.5),
+            tri(WHITE, 0.3),
+        ];
+
+        let order: Vec<(bool, f64)> =
+            camera.sorted().iter().map(|t| (t.color().a < 1.0, t.points()[0].z())).collect();
+        // opaque near to far, then transparent far to near
+        assert_eq!(
+            order,
+            vec![(false, 0.1), (false, 0.3), (false, 0.6), (true, 0.9), (true, 0.5), (true, 0.2)]
+        );
+
+        // render priority still comes first
+        let mut front = tri(glass, 0.95);
+        *front.render_priority_mut() = 1;
+        camera.triangles.push(front);
+        assert_eq!(camera.sorted_incremental().last().unwrap().points()[0].z(), 0.95);
+        assert!(paint_cmp(&tri(WHITE, 0.9), &tri(glass, 0.1)).is_lt());
+    }
+
+    #[test]
+    fn clipped_face_keeps_shading() {
+        let mut mesh = Cube::new(ObjectNameTag::new("Face"), 1.0, WHITE);
+        // big slanted face crossing the near plane and the sides of the frustum
+        *mesh.triangles_mut() = vec![Triangle::new(
+            Color::new(0.5, 0.5, 0.5, 1.0),
+            Vec4d::new(-20.0, -1.0, -5.0, 1.0),
+            Vec4d::new(0.0, 3.0, 10.0, 1.0),
+            Vec4d::new(20.0, -1.0, 3.0, 1.0),
+        )];
+
+        let mut camera = camera();
+        let tris = camera.project(Rc::new(RefCell::new(mesh)));
+
+        assert!(tris.len() > 1);
+        for tri in &tris {
+            assert_eq!(tri.color(), tris[0].color());
+        }
+    }
+
+    #[test]
+    fn custom_shader() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, Color::new(1.0, 0.0, 0.0, 1.0));
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+
+        let mut camera = camera();
+        camera.set_shader(Box::new(|_| WHITE));
+        let tris = camera.project(Rc::new(RefCell::new(cube)));
+
+        assert!(!tris.is_empty());
+        for tri in tris.iter() {
+            assert_eq!(tri.color(), WHITE);
+        }
+    }
+
+    #[test]
+    fn gouraud_shading() {
+        let corners = [
+            Vec4d::new(-1.0, -1.0, 3.0, 1.0),
+            Vec4d::new(1.0, -1.0, 3.0, 1.0),
+            Vec4d::new(0.0, 1.0, 3.0, 1.0),
+        ];
+        let mut smooth = Triangle::new(WHITE, corners[0], corners[1], corners[2]);
+        // facing the light, 60 degrees from it and perpendicular to it
+        let tilted = Vec3d::new((PI / 3.0).sin(), 0.0, -(PI / 3.0).cos());
+        *smooth.vertex_normals_mut() = Some([Vec3d::new(0.0, 0.0, -1.0), tilted, Vec3d::new(1.0, 0.0, 0.0)]);
+        let flat = Triangle::new(WHITE, corners[0], corners[2], corners[1]);
+        let mesh = LoadedMesh::new(ObjectNameTag::new("Mesh"), vec![smooth.clone(), flat], WHITE);
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 1.0, WHITE)]);
+        let out = camera.project(Rc::new(RefCell::new(mesh)));
+
+        let ambient = LIGHT_AMBIENT as f32;
+        // vertex colors stay linear, above 1 where the light and the ambient sum up
+        let colors = out[0].vertex_colors().unwrap();
+        assert!((colors[0].r - (1.0 + ambient)).abs() < 1e-6);
+        assert!((colors[1].r - (ambient + 0.5)).abs() < 1e-6);
+        assert_eq!(colors[2], Color::new(ambient, ambient, ambient, 1.0));
+        assert!((out[0].color().r - (1.0 + ambient + ambient + 0.5 + ambient) / 3.0).abs() < 1e-6);
+        // no vertex normals, flat shaded as before
+        assert!(out[1].vertex_colors().is_none() && out[1].color() == WHITE);
+
+        // the rasterizer blends them: bright near the first vertex, dark near the last
+        camera.clear();
+        camera.project(Rc::new(RefCell::new(LoadedMesh::new(ObjectNameTag::new("Smooth"), vec![smooth], WHITE))));
+        let pixels = camera.rasterize(800, 600);
+        let near = |i: usize| {
+            let p = out[0].points()[i] + (out[0].position().make_point_4d() - out[0].points()[i]) * 0.1;
+            p.y() as usize * 800 + p.x() as usize
+        };
+        assert!(pixels[near(0)].r > 0.9 && pixels[near(2)].r < 0.3 && pixels[near(1)].r > pixels[near(2)].r);
+
+        // posterizing bands the blend per pixel instead of only snapping the vertex colors
+        camera.set_posterize(2);
+        let pixels = camera.rasterize(800, 600);
+        assert!(pixels.iter().all(|c| c.r == 0.0 || c.r == 1.0));
+        assert!(pixels[near(0)].r == 1.0 && pixels[near(2)].r == 0.0);
+    }
+
+    #[test]
+    fn flat_vertex_colors_are_lit() {
+        let colors = [Color::new(1.0, 0.0, 0.0, 1.0), Color::new(0.0, 1.0, 0.0, 1.0), Color::new(0.0, 0.0, 1.0, 1.0)];
+        let tri = Triangle::new_shaded(
+            colors,
+            Vec4d::new(-1.0, -1.0, 3.0, 1.0),
+            Vec4d::new(0.0, 1.0, 3.0, 1.0),
+            Vec4d::new(1.0, -1.0, 3.0, 1.0),
+        );
+        let mesh = LoadedMesh::new(ObjectNameTag::new("Mesh"), vec![tri], WHITE);
+
+        let mut camera = camera();
+        camera.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 0.5, WHITE)]);
+        let out = camera.project(Rc::new(RefCell::new(mesh)));
+
+        // every vertex lit with the face normal, not drawn with the raw colors
+        let lit = LIGHT_AMBIENT as f32 + 0.5;
+        let shaded = out[0].vertex_colors().unwrap();
+        for (shaded, color) in shaded.iter().zip(colors) {
+            assert!((shaded.r - color.r * lit).abs() < 1e-6);
+            assert!((shaded.g - color.g * lit).abs() < 1e-6);
+            assert!((shaded.b - color.b * lit).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn directional_lights() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        let faces = |camera: &MyCamera| {
+            let mut out = vec![];
+            camera.project_to(&cube, &mut out);
+            // the two triangles of each face, -Z, +X, +Z, -X, +Y, -Y
+            out.chunks(2).map(|pair| pair[0].color()).collect::<Vec<Color>>()
+        };
+
+        // shining along +Z straight at the -Z face, at 60 degrees from the +X face
+        let direction = Vec3d::new(-(PI / 6.0).sin(), 0.0, (PI / 6.0).cos());
+        camera.set_lights(vec![DirectionalLight::new(direction, 0.5, Color::new(1.0, 0.5, 1.0, 1.0))]);
+        let lit = faces(&camera);
+        let ambient = LIGHT_AMBIENT as f32;
+        let front = ambient + 0.5 * (PI / 6.0).cos() as f32;
+        assert!((lit[0].r - front).abs() < 1e-6 && (lit[0].g - (ambient + (front - ambient) / 2.0)).abs() < 1e-6);
+        assert!((lit[1].r - (ambient + 0.25)).abs() < 1e-6);
+        // turned away from the light
+        assert_eq!(lit[2], Color::new(ambient, ambient, ambient, 1.0));
+
+        // summed and clamped
+        camera.set_lights(vec![DirectionalLight::new(direction, 2.0, WHITE); 2]);
+        let lit = faces(&camera);
+        assert_eq!(lit[0], WHITE);
+        assert_eq!(lit[4], Color::new(ambient, ambient, ambient, 1.0));
+
+        // back to the view dependent shading, at least 70% of the color
+        camera.set_lights(vec![]);
+        assert!(faces(&camera).iter().all(|c| c.r >= 0.7));
+    }
+
+    #[test]
+    fn reinhard_tone_mapping() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, Color::new(0.5, 0.5, 0.5, 1.0));
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        // two lights of intensity 1.5 each straight at the front face, summing to 3 over the ambient
+        let mut camera = camera();
+        camera.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 1.5, WHITE); 2]);
+        camera.project(cube.clone());
+        let center = 300 * 800 + 400;
+        assert_eq!(camera.render_to_image(800, 600)[center][0], 255);
+
+        camera.clear();
+        camera.set_tone_mapping(ToneMap::Reinhard, 1.0);
+        camera.project(cube);
+        let pixel = camera.render_to_image(800, 600)[center];
+        let c = 0.5 * (LIGHT_AMBIENT + 3.0) as f32;
+        assert_eq!(pixel[0], (c / (c + 1.0) * 255.0).round() as u8);
+        assert_eq!(pixel[3], 255);
+    }
+
+    #[test]
+    fn progressive_rows() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 2.0));
+
+        let mut camera = camera();
+        camera.set_progressive(2);
+        camera.project(Rc::new(RefCell::new(cube)));
+
+        let (w, h) = (80, 60);
+        let row = |pixels: &[Color], y: usize| pixels[y * w..(y + 1) * w].to_vec();
+        let first = camera.rasterize(w as i32, h as i32);
+        for y in 0..h {
+            if y % 2 == 1 {
+                assert!(row(&first, y).iter().all(|c| *c == BLANK));
+            }
+        }
+        assert_ne!(first[30 * w + 40], BLANK);
+
+        // the next call fills in the other rows and keeps the first ones
+        let second = camera.rasterize(w as i32, h as i32);
+        assert_ne!(second[31 * w + 40], BLANK);
+        for y in (0..h).step_by(2) {
+            assert_eq!(row(&first, y), row(&second, y));
+        }
+    }
+
+    #[test]
+    fn posterize_levels() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, Color::new(0.8, 0.45, 0.2, 1.0));
+        cube.rotate(&Vec3d::new(0.5, 0.7, 0.0));
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        let mut camera = camera();
+        let plain = camera.project(cube.clone());
+        camera.clear();
+        camera.set_posterize(255);
+        let off = camera.project(cube.clone());
+        assert!(plain.iter().zip(off.iter()).all(|(a, b)| a.color() == b.color()));
+        assert!(plain.iter().any(|t| t.color().g != 0.0 && t.color().g != 1.0));
+
+        camera.clear();
+        camera.set_posterize(2);
+        camera.project(cube);
+        for pixel in camera.render_to_image(160, 120) {
+            assert!(pixel.iter().all(|&c| c == 0 || c == 255));
+        }
+    }
+
+    #[test]
+    fn ascii_render() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 0.0, 3.0));
+        let mut camera = camera();
+        camera.project(Rc::new(RefCell::new(cube)));
+
+        let ascii = camera.render_ascii(40, 20);
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 20);
+        assert!(lines.iter().all(|line| line.len() == 40));
+
+        for line in &lines[8..12] {
+            assert!(line[16..24].chars().all(|c| c != ' '));
+        }
+        for (x, y) in [(0, 0), (39, 0), (0, 19), (39, 19)] {
+            assert_eq!(lines[y].as_bytes()[x], b' ');
+        }
+    }
+
+    #[test]
+    fn world_clip_planes() {
+        let mut camera = camera();
+        camera.translate(&Vec3d::new(1.0, 2.0, 3.0));
+        camera.rotate(&Vec3d::new(0.3, 1.2, 0.0));
+
+        let planes = camera.world_clip_planes();
+        assert_eq!(planes.len(), 6);
+
+        let in_front = camera.focus_point(5.0);
+        assert!(planes.iter().all(|plane| plane.distance(&in_front) >= 0.0));
+
+        let behind = camera.focus_point(-5.0);
+        assert!(planes[0].distance(&behind) < 0.0);
+    }
+
+    #[test]
+    fn render_mode() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, RED);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+
+        let mut camera = camera();
+        camera.set_wireframe(2.0, false);
+        camera.project(Rc::new(RefCell::new(cube)));
+        // (red, black) pixel counts, the fill is red and the edges black
+        let mut count = |mode: RenderMode| {
+            camera.set_render_mode(mode);
+            let pixels = camera.rasterize(800, 600);
+            let filled = pixels.iter().filter(|c| c.r > 0.0).count();
+            (filled, pixels.iter().filter(|c| c.a > 0.0 && (c.r, c.g, c.b) == (0.0, 0.0, 0.0)).count())
+        };
+
+        let (filled, edges) = count(RenderMode::Filled);
+        assert!(filled > 0 && edges == 0);
+        let (filled, edges) = count(RenderMode::Wireframe);
+        assert!(filled == 0 && edges > 0);
+        let (filled, edges) = count(RenderMode::FilledWireframe);
+        assert!(filled > 0 && edges > 0);
+
+        assert_eq!(RenderMode::Filled.next().next().next(), RenderMode::Filled);
+    }
+
+    #[test]
+    fn export_svg() {
+        let mut color = RED;
+        color.a = 0.5;
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, color);
+        cube.translate(&Vec3d::new(0.0, 0.0, 5.0));
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        // lights keep the alpha as it is
+        camera.set_lights(vec![DirectionalLight::new(Vec3d::new(0.0, 0.0, 1.0), 1.0, WHITE)]);
+        camera.project(Rc::new(RefCell::new(cube)));
+
+        let svg = camera.export_svg(400, 300);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="300""#));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polygon").count(), camera.buffsize());
+        assert_eq!(svg.matches(r#"fill-opacity="0.5""#).count(), camera.buffsize());
+        assert!(!svg.contains("stroke"));
+
+        // the cube is in the middle of the 800 x 600 viewport, halved to the middle of 400 x 300
+        let first = svg.lines().nth(1).unwrap();
+        let x: f64 = first.split('"').nth(1).unwrap().split(',').next().unwrap().parse().unwrap();
+        assert!((x - 200.0).abs() < 50.0);
+
+        camera.set_render_mode(RenderMode::Wireframe);
+        camera.set_wireframe(1.5, false);
+        let svg = camera.export_svg(400, 300);
+        assert_eq!(svg.matches(r#"fill="none" stroke="black" stroke-width="1.5""#).count(), camera.buffsize());
+    }
+
+    #[test]
+    fn hidden_line_removal() {
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let front = Triangle::new(
+            red,
+            Vec4d::new(-5.0, -5.0, 5.0, 1.0),
+            Vec4d::new(5.0, -5.0, 5.0, 1.0),
+            Vec4d::new(0.0, 5.0, 5.0, 1.0),
+        );
+        // its first edge crosses the center of the screen behind the front face
+        let back = Triangle::new(
+            red,
+            Vec4d::new(0.0, -3.0, 10.0, 1.0),
+            Vec4d::new(0.0, 3.0, 10.0, 1.0),
+            Vec4d::new(2.0, 0.0, 10.0, 1.0),
+        );
+        let mesh = LoadedMesh::new(ObjectNameTag::new("Mesh"), vec![front, back], red);
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.project(Rc::new(RefCell::new(mesh)));
+        let center = |pixels: &[Color]| pixels[300 * 800 + 400];
+
+        camera.set_wireframe(3.0, false);
+        let shaded = center(&camera.rasterize(800, 600));
+        assert_eq!((shaded.r, shaded.g, shaded.b), (0.0, 0.0, 0.0));
+
+        camera.set_wireframe(3.0, true);
+        let hidden = center(&camera.rasterize(800, 600));
+        assert!(hidden.r > 0.5);
+    }
+
+    #[test]
+    fn perspective_correct_uvs() {
+        // vertical ramp: row i of the texture has green i / 63
+        let texels = (0..64).map(|i| Color::new(0.0, i as f32 / 63.0, 0.0, 1.0)).collect();
+        let mut material = Material::new(WHITE);
+        *material.texture_mut() = Some(Arc::new(Texture::new(1, 64, texels)));
+
+        // floor from z = 2 to z = 22, v grows with the distance
+        let corner = |x: f64, z: f64| Vec4d::new(x, -1.0, z, 1.0);
+        let uv = |x: f64, z: f64| Vec2d::new((x + 1.0) / 2.0, (z - 2.0) / 20.0);
+        let mut floor = vec![
+            Triangle::new(WHITE, corner(-1.0, 2.0), corner(1.0, 2.0), corner(1.0, 22.0)),
+            Triangle::new(WHITE, corner(-1.0, 2.0), corner(1.0, 22.0), corner(-1.0, 22.0)),
+        ];
+        *floor[0].uvs_mut() = Some([uv(-1.0, 2.0), uv(1.0, 2.0), uv(1.0, 22.0)]);
+        *floor[1].uvs_mut() = Some([uv(-1.0, 2.0), uv(1.0, 22.0), uv(-1.0, 22.0)]);
+        for tri in floor.iter_mut() {
+            *tri.material_id_mut() = Some(0);
+        }
+        let mut mesh = LoadedMesh::new(ObjectNameTag::new("Floor"), floor, WHITE);
+        *mesh.materials_mut() = vec![material];
+
+        let mut camera = camera();
+        camera.set_cull_backfaces(false);
+        camera.set_shader(Box::new(|_| WHITE));
+        camera.project(Rc::new(RefCell::new(mesh)));
+
+        // screen position of the middle of the floor, where v = 0.5
+        let mid = &camera.sp * &Vec4d::new(0.0, -1.0, 12.0, 1.0);
+        let pixel = (mid.y() / mid.w()) as usize * 800 + (mid.x() / mid.w()) as usize;
+
+        let correct = camera.rasterize(800, 600)[pixel];
+        assert!((correct.g - 0.5).abs() < 0.05);
+
+        camera.set_perspective_correct(false);
+        let affine = camera.rasterize(800, 600)[pixel];
+        assert!((affine.g - 0.5).abs() > 0.2);
+    }
+
+    #[test]
+    fn clear_color() {
+        let mut camera = camera();
+        let background = Color::new(0.2, 0.4, 0.6, 1.0);
+        camera.set_clear_color(background);
+        camera.set_clear_depth(1.0);
+
+        let pixels = camera.rasterize(80, 60);
+        assert_eq!(pixels.len(), 80 * 60);
+        assert!(pixels.iter().all(|&p| p == background));
+
+        assert!(camera.render_to_image(8, 6).iter().all(|p| *p == [51, 102, 153, 255]));
+    }
+
+    #[test]
+    fn debug_project() {
+        let mut camera = camera();
+        camera.translate(&Vec3d::new(1.0, 0.0, -1.0));
+        let trace = camera.debug_project(&Vec3d::new(2.0, 1.0, 1.0));
+
+        assert_eq!(trace.view, Vec4d::new(1.0, 1.0, 2.0, 1.0));
+        // tan(45) = 1, aspect 4 / 3, the projection matrix keeps the identity's 1 in [3][3] so w = z + 1
+        let z = (100.0 * 2.0 - 100.0 * 0.1) / (100.0 - 0.1);
+        assert_eq!(trace.clip, Vec4d::new(0.75, 1.0, z, 3.0));
+        assert_eq!(trace.ndc, Vec4d::new(0.25, 1.0 / 3.0, z / 3.0, 1.0));
+        // x and y flipped: 400 - 400 * 0.25, 300 - 300 / 3
+        assert_eq!(trace.screen, Vec4d::new(300.0, 200.0, z / 3.0, 1.0));
+
+        let tri = Triangle::new(WHITE, trace.view, trace.view, trace.view);
+        assert_eq!((&tri * &camera.sp).points()[0] / 3.0, trace.screen);
+    }
+
+    #[test]
+    fn screen_drag_to_world() {
+        let mut camera = camera();
+        camera.rotate_up(0.3);
+        let start = camera.focus_point(5.0) + Vec3d::new(0.2, -0.4, 0.1);
+        let screen = camera.debug_project(&start).screen;
+
+        let right = camera.screen_drag_to_world(&start, 40.0, 0.0);
+        assert!(right.normalized().dot(&-camera.left()) > 1.0 - 1e-12);
+        let down = camera.screen_drag_to_world(&start, 0.0, 25.0);
+        assert!(down.normalized().dot(&-camera.up()) > 1.0 - 1e-12);
+
+        let moved = camera.debug_project(&(start + (right + down))).screen;
+        assert!((moved.x() - screen.x() - 40.0).abs() < 1e-9);
+        assert!((moved.y() - screen.y() - 25.0).abs() < 1e-9);
+
+        let far = camera.focus_point(20.0);
+        assert!(camera.screen_drag_to_world(&far, 40.0, 0.0).abs() > 2.0 * right.abs());
+    }
+
+    #[test]
+    fn look_at_point() {
+        let mut camera = camera();
+        camera.translate(&Vec3d::new(3.0, 2.0, -4.0));
+        let target = Vec3d::new(-1.0, 0.5, 6.0);
+        camera.look_at_point(&target, &Vec3d::new(0.0, 1.0, 0.0));
+
+        // target lands in the middle of the screen, the horizon stays level
+        let screen = camera.debug_project(&target).screen;
+        assert!((screen.x() - 400.0).abs() < 1e-9 && (screen.y() - 300.0).abs() < 1e-9);
+        assert!(camera.left().y().abs() < 1e-12 && camera.up().y() > 0.0);
+        assert!(!camera.transform_matrix().is_mirroring());
+
+        // the tracked angles describe the same look direction
+        let angle = *camera.angle_left_up_look_at();
+        let expected = Matrix4x4::rotation_around_vec(&Vec3d::new(0.0, 1.0, 0.0), angle.y())
+            * Matrix4x4::rotation_around_vec(&Vec3d::new(1.0, 0.0, 0.0), angle.x());
+        assert!((expected.z() - camera.look_at()).abs() < 1e-12 && angle.z() == 0.0);
+
+        // straight up along the up hint still gives an orthonormal basis
+        camera.look_at_point(&(*camera.position() + Vec3d::new(0.0, 7.0, 0.0)), &Vec3d::new(0.0, 1.0, 0.0));
+        assert_eq!(camera.look_at(), Vec3d::new(0.0, 1.0, 0.0));
+        let (left, up) = (camera.left(), camera.up());
+        assert!(left.dot(&up).abs() < 1e-12 && (left.abs() - 1.0).abs() < 1e-12 && (up.abs() - 1.0).abs() < 1e-12);
+        assert!(!camera.transform_matrix().is_mirroring());
+    }
+
+    #[test]
+    fn screen_bounds() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.5, -0.5, 5.0));
+
+        let mut camera = camera();
+        let (min, max) = camera.screen_bounds(&cube).unwrap();
+        let tris = camera.project(Rc::new(RefCell::new(cube)));
+
+        assert!(min.x() < max.x() && min.y() < max.y());
+        for p in tris.iter().flat_map(|t| t.points().iter()) {
+            assert!(p.x() >= min.x() && p.x() <= max.x());
+            assert!(p.y() >= min.y() && p.y() <= max.y());
+        }
+
+        let mut behind = Cube::new(ObjectNameTag::new("Behind"), 1.0, WHITE);
+        behind.translate(&Vec3d::new(0.0, 0.0, -5.0));
+        assert!(camera.screen_bounds(&behind).is_none());
+    }
+
+    #[test]
+    fn pixel_snap() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(0.3, 0.1, 4.7));
+        cube.rotate(&Vec3d::new(0.2, 0.4, 0.0));
+        let cube = Rc::new(RefCell::new(cube));
+
+        let mut camera = camera();
+        let smooth = camera.project(cube.clone());
+        assert!(smooth.iter().flat_map(|t| t.points().iter()).any(|p| p.x().fract() != 0.0));
+        camera.clear();
+
+        camera.set_pixel_snap(true);
+        let snapped = camera.project(cube);
+        assert_eq!(snapped.len(), smooth.len());
+        for p in snapped.iter().flat_map(|t| t.points().iter()) {
+            assert_eq!(p.x().fract(), 0.0);
+            assert_eq!(p.y().fract(), 0.0);
+        }
+    }
+}
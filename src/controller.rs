@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::KeyCode;
+
+use crate::math::vec_3d::Vec3d;
+use crate::object::Object;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    // flips through the camera's RenderMode, meant to be checked with a pressed (not held) key
+    CycleRenderMode,
+    Quit,
+}
+
+// Maps actions to key chords: an action is active while all of its keys are held.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl InputMap {
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn bind(&mut self, action: Action, keys: &[KeyCode]) {
+        self.bindings.insert(action, keys.to_vec());
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn keys(&self, action: Action) -> Option<&Vec<KeyCode>> {
+        self.bindings.get(&action)
+    }
+
+    pub fn is_active(&self, action: Action, is_down: impl Fn(KeyCode) -> bool) -> bool {
+        match self.bindings.get(&action) {
+            Some(keys) => !keys.is_empty() && keys.iter().all(|key| is_down(*key)),
+            None => false,
+        }
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.bind(Action::MoveForward, &[KeyCode::W]);
+        map.bind(Action::MoveBackward, &[KeyCode::S]);
+        map.bind(Action::MoveLeft, &[KeyCode::A]);
+        map.bind(Action::MoveRight, &[KeyCode::D]);
+        map.bind(Action::Jump, &[KeyCode::Space]);
+        map.bind(Action::Crouch, &[KeyCode::LeftShift]);
+        map.bind(Action::CycleRenderMode, &[KeyCode::Tab]);
+        map.bind(Action::Quit, &[KeyCode::LeftControl, KeyCode::Q]);
+        map
+    }
+}
+
+pub struct CameraController {
+    pub input_map: InputMap,
+    pub speed: f64,
+    // look smoothing time constant in seconds, 0 applies look deltas at once
+    pub smoothing: f64,
+    // (yaw, pitch) received but not applied yet
+    pending_look: (f64, f64),
+}
+
+impl CameraController {
+    pub fn new(input_map: InputMap, speed: f64) -> Self {
+        Self {
+            input_map,
+            speed,
+            smoothing: 0.0,
+            pending_look: (0.0, 0.0),
+        }
+    }
+
+    // Adds yaw / pitch deltas (radians, e.g. scaled mouse movement) and turns the object by
+    // the part of the pending rotation due this frame: with smoothing it eases out exponentially,
+    // 1 - exp(-dt / smoothing) of what is left each update. Only depends on the inputs and dt.
+    pub fn apply_look(&mut self, object: &mut dyn Object, yaw: f64, pitch: f64, dt: f64) {
+        self.pending_look.0 += yaw;
+        self.pending_look.1 += pitch;
+
+        let k = if self.smoothing > 0.0 {
+            1.0 - (-dt / self.smoothing).exp()
+        } else {
+            1.0
+        };
+        let (yaw, pitch) = (self.pending_look.0 * k, self.pending_look.1 * k);
+        self.pending_look.0 -= yaw;
+        self.pending_look.1 -= pitch;
+
+        object.rotate_up(yaw);
+        object.rotate_left(pitch);
+    }
+
+    // Moves the object along the world axes according to the active actions.
+    // is_down reports whether a key is held (macroquad's is_key_down in the app).
+    pub fn apply_input(&self, object: &mut dyn Object, is_down: impl Fn(KeyCode) -> bool, dt: f64) {
+        let step = self.speed * dt;
+        let moves = [
+            (Action::MoveForward, Vec3d::new(0.0, 0.0, step)),
+            (Action::MoveBackward, Vec3d::new(0.0, 0.0, -step)),
+            (Action::MoveLeft, Vec3d::new(step, 0.0, 0.0)),
+            (Action::MoveRight, Vec3d::new(-step, 0.0, 0.0)),
+            (Action::Jump, Vec3d::new(0.0, step, 0.0)),
+            (Action::Crouch, Vec3d::new(0.0, -step, 0.0)),
+        ];
+
+        for (action, dv) in moves {
+            if self.input_map.is_active(action, &is_down) {
+                object.translate(&dv);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::KeyCode;
+
+    use crate::camera::MyCamera;
+    use crate::math::vec_3d::Vec3d;
+    use crate::object::Object;
+
+    use super::{Action, CameraController, InputMap};
+
+    #[test]
+    fn remapped_forward() {
+        let mut map = InputMap::default();
+        map.bind(Action::MoveForward, &[KeyCode::Up]);
+        let controller = CameraController::new(map, 5.0);
+        let mut camera = MyCamera::new("Camera");
+
+        controller.apply_input(&mut camera, |key| key == KeyCode::W, 1.0);
+        assert_eq!(*camera.position(), Vec3d::new(0.0, 0.0, 0.0));
+
+        controller.apply_input(&mut camera, |key| key == KeyCode::Up, 1.0);
+        assert_eq!(*camera.position(), Vec3d::new(0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn smoothed_look() {
+        let mut controller = CameraController::new(InputMap::default(), 5.0);
+        let mut camera = MyCamera::new("Camera");
+        controller.apply_look(&mut camera, 1.0, 0.0, 1.0 / 60.0);
+        assert!((camera.angle_left_up_look_at().y() - 1.0).abs() < 1e-12);
+
+        controller.smoothing = 0.2;
+        let mut camera = MyCamera::new("Camera");
+        let mut yaws = vec![];
+        controller.apply_look(&mut camera, 1.0, 0.0, 1.0 / 60.0);
+        yaws.push(camera.angle_left_up_look_at().y());
+        for _ in 0..59 {
+            controller.apply_look(&mut camera, 0.0, 0.0, 1.0 / 60.0);
+            yaws.push(camera.angle_left_up_look_at().y());
+        }
+
+        assert!(yaws[0] < 0.1);
+        assert!(yaws.windows(2).all(|w| w[1] > w[0]));
+        // about 5 time constants in, nearly all of it is applied
+        assert!(yaws[59] > 0.99 && yaws[59] < 1.0);
+    }
+
+    #[test]
+    fn quit_chord() {
+        let map = InputMap::default();
+        assert!(!map.is_active(Action::Quit, |key| key == KeyCode::Q));
+        assert!(map.is_active(Action::Quit, |key| key == KeyCode::Q || key == KeyCode::LeftControl));
+    }
+}
@@ -1,4 +1,13 @@
+pub mod adjacency;
 pub mod camera;
+pub mod controller;
+pub mod material;
+pub mod light;
+pub mod loader;
 pub mod math;
+pub mod mesh_loader;
 pub mod object;
+pub mod profile;
+pub mod raster;
+pub mod scene;
 pub mod triangle;
@@ -0,0 +1,84 @@
+use std::f64::consts::PI;
+
+use macroquad::prelude::{Color, WHITE};
+
+use crate::material::lerp_color;
+use crate::math::vec_3d::Vec3d;
+
+// color of the sun right at the horizon
+const HORIZON_SUN: Color = Color::new(1.0, 0.55, 0.25, 1.0);
+
+// Directional light: parallel rays travelling along direction (unit length).
+#[derive(Debug, Clone)]
+pub struct Light {
+    direction: Vec3d,
+    intensity: f64,
+    color: Color,
+}
+
+pub type DirectionalLight = Light;
+
+impl Light {
+    pub fn new(direction: Vec3d, intensity: f64, color: Color) -> Self {
+        Self {
+            direction: direction.normalized(),
+            intensity,
+            color,
+        }
+    }
+
+    // Sun at time_of_day hours: rises in +X at 6, straight overhead (shining down -Y) at 12,
+    // sets in -X at 18. Near the horizon it turns orange, from about 30 degrees up it is white.
+    // At night it's below the horizon with zero intensity.
+    pub fn sun(time_of_day: f64) -> Self {
+        let angle = (time_of_day - 6.0) / 12.0 * PI;
+        let elevation = angle.sin();
+        let to_sun = Vec3d::new(angle.cos(), elevation, 0.0);
+
+        let warmth = (1.0 - 2.0 * elevation).clamp(0.0, 1.0) as f32;
+        Self::new(-to_sun, elevation.max(0.0), lerp_color(WHITE, HORIZON_SUN, warmth))
+    }
+
+    // Lambertian term for a surface with the given unit normal: how much light of each color channel
+    // it receives, zero when it faces away
+    pub fn irradiance(&self, normal: &Vec3d) -> [f64; 3] {
+        let lambert = normal.dot(&-&self.direction).max(0.0) * self.intensity;
+        [self.color.r, self.color.g, self.color.b].map(|c| lambert * c as f64)
+    }
+
+    pub fn direction(&self) -> &Vec3d {
+        &self.direction
+    }
+
+    pub fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::vec_3d::Vec3d;
+
+    use super::Light;
+
+    #[test]
+    fn sun_over_the_day() {
+        let noon = Light::sun(12.0);
+        assert!(noon.direction().dot(&Vec3d::new(0.0, -1.0, 0.0)) > 0.999);
+        assert!((noon.intensity() - 1.0).abs() < 1e-9);
+        let c = noon.color();
+        assert!(c.r > 0.99 && c.g > 0.99 && c.b > 0.99);
+
+        let dawn = Light::sun(6.5);
+        assert!(dawn.direction().y() > -0.2 && dawn.direction().y() < 0.0);
+        assert!(dawn.intensity() < noon.intensity());
+        let c = dawn.color();
+        assert!(c.r > c.g && c.g > c.b);
+
+        assert_eq!(Light::sun(0.0).intensity(), 0.0);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use macroquad::prelude::Color;
+
+use crate::math::vec_4d::Vec4d;
+use crate::mesh_loader::invalid;
+use crate::object::{LoadedMesh, ObjectNameTag};
+use crate::triangle::Triangle;
+
+// the mesh load_obj returns, an Object + Mesh like any other loaded mesh
+pub type ObjMesh = LoadedMesh;
+
+// Loads a Wavefront OBJ mesh named after the file: v and f lines, everything else (uvs, normals, groups,
+// materials) is ignored. Face vertices may use the v/vt/vn forms and negative indices counting back from
+// the last vertex, polygons are split into triangle fans.
+pub fn load_obj(path: &str, color: Color) -> io::Result<ObjMesh> {
+    let text = fs::read_to_string(path)?;
+    let name = Path::new(path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(path);
+    parse_obj(&text, ObjectNameTag::new(name), color)
+}
+
+// same as load_obj, from the file contents
+pub fn parse_obj(text: &str, nametag: ObjectNameTag, color: Color) -> io::Result<ObjMesh> {
+    let mut positions: Vec<Vec4d> = vec![];
+    let mut triangles = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let err = |msg: String| invalid(format!("OBJ line {}: {}", number + 1, msg));
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => {
+                let xyz: Vec<f64> = words
+                    .take(3)
+                    .map(|w| w.parse().map_err(|_| err(format!("expected a number, found '{}'", w))))
+                    .collect::<io::Result<_>>()?;
+                if xyz.len() < 3 {
+                    return Err(err(format!("vertex needs x, y and z: '{}'", line.trim())));
+                }
+                positions.push(Vec4d::new(xyz[0], xyz[1], xyz[2], 1.0));
+            }
+            Some("f") => {
+                let face: Vec<usize> = words
+                    .map(|w| {
+                        let index: i64 = w
+                            .split('/')
+                            .next()
+                            .and_then(|i| i.parse().ok())
+                            .ok_or_else(|| err(format!("bad face vertex '{}'", w)))?;
+                        let resolved = if index < 0 { positions.len() as i64 + index } else { index - 1 };
+                        if resolved < 0 || resolved >= positions.len() as i64 {
+                            return Err(err(format!("face references missing vertex {}", index)));
+                        }
+                        Ok(resolved as usize)
+                    })
+                    .collect::<io::Result<_>>()?;
+                if face.len() < 3 {
+                    return Err(err(format!("face needs at least 3 vertices: '{}'", line.trim())));
+                }
+
+                for k in 1..face.len() - 1 {
+                    triangles.push(Triangle::new(
+                        color,
+                        positions[face[0]],
+                        positions[face[k]],
+                        positions[face[k + 1]],
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(LoadedMesh::new(nametag, triangles, color))
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::WHITE;
+
+    use crate::object::{Mesh, Object, ObjectNameTag};
+
+    use super::{load_obj, parse_obj};
+
+    #[test]
+    fn obj_faces() {
+        let text = "# quad and a relative triangle
+o Quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+f 1/1/1 2/1/1 3//1 4
+v 2 0 0
+f -1 -4 -3
+";
+        let path = std::env::temp_dir().join("rust_3d_course_quad.obj");
+        std::fs::write(&path, text).unwrap();
+        let mesh = load_obj(path.to_str().unwrap(), WHITE).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mesh.nametag().name(), "rust_3d_course_quad");
+        assert_eq!(mesh.triangles().len(), 3);
+        assert_eq!(mesh.triangles()[1].points()[2].y(), 1.0);
+        let last = mesh.triangles()[2].points();
+        assert_eq!((last[0].x(), last[1].x(), last[2].x()), (2.0, 1.0, 1.0));
+        assert_eq!(last[0].w(), 1.0);
+
+        for bad in ["v 1 2", "v 1 a 2", "v 0 0 0\nf 1 2", "v 0 0 0\nf 1 1 4", "v 0 0 0\nf 1 1 -2", "f x 1 1"] {
+            let err = parse_obj(bad, ObjectNameTag::new("Bad"), WHITE).err().unwrap();
+            assert!(err.to_string().starts_with("OBJ line"), "{}", err);
+        }
+    }
+}
@@ -3,8 +3,9 @@ use std::rc::Rc;
 
 use macroquad::prelude::*;
 use rust_3d_course::camera::MyCamera;
+use rust_3d_course::controller::{Action, CameraController, InputMap};
 use rust_3d_course::math::vec_3d::Vec3d;
-use rust_3d_course::object::{Cube, Object, ObjectNameTag};
+use rust_3d_course::object::{Cube, Object, ObjectNameTag, PlaneMesh};
 
 fn window_conf() -> Conf {
     Conf {
@@ -24,31 +25,24 @@ async fn main() {
     let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.5, color);
     cube.translate(&Vec3d::new(0.0, 0.0, 7.));
     let cube = Rc::new(RefCell::new(cube));
+    let mut floor = PlaneMesh::new(ObjectNameTag::new("Floor"), 40., 40., 20, 20, GRAY);
+    floor.translate(&Vec3d::new(0.0, -3., 7.));
+    let floor = Rc::new(RefCell::new(floor));
     let mut camera = MyCamera::new("Camera");
-    camera.init(screen_width() as i32, screen_height() as i32, 90.0, -10., 500.);
+    // a positive near plane keeps screen depth growing away from the camera, which the sort relies on
+    camera.init(screen_width() as i32, screen_height() as i32, 90.0, 0.1, 500.);
+    // the cube is see-through, its back faces have to be drawn too
+    camera.set_cull_backfaces(false);
+    let controller = CameraController::new(InputMap::default(), 5.0);
 
     loop {
-        if is_key_down(KeyCode::Q) && is_key_down(KeyCode::LeftControl) {
+        if controller.input_map.is_active(Action::Quit, is_key_down) {
             break;
         }
 
-        if is_key_down(KeyCode::D) {
-            camera.translate(&Vec3d::new(-5. * get_frame_time() as f64, 0.0, 0.0));
-        }
-        if is_key_down(KeyCode::A) {
-            camera.translate(&Vec3d::new(5. * get_frame_time() as f64, 0.0, 0.0));
-        }
-        if is_key_down(KeyCode::W) {
-            camera.translate(&Vec3d::new(0.0, 0.0, 5. * get_frame_time() as f64));
-        }
-        if is_key_down(KeyCode::S) {
-            camera.translate(&Vec3d::new(0.0, 0.0, -5. * get_frame_time() as f64));
-        }
-        if is_key_down(KeyCode::Space) {
-            camera.translate(&Vec3d::new(0.0, 5. * get_frame_time() as f64, 0.0));
-        }
-        if is_key_down(KeyCode::LeftShift) {
-            camera.translate(&Vec3d::new(0.0, -5. * get_frame_time() as f64, 0.0));
+        controller.apply_input(&mut camera, is_key_down, get_frame_time() as f64);
+        if controller.input_map.is_active(Action::CycleRenderMode, is_key_pressed) {
+            camera.set_render_mode(camera.render_mode().next());
         }
 
         clear_background(LIGHTGRAY);
@@ -60,16 +54,24 @@ async fn main() {
             0.25 * get_frame_time() as f64 * 0.5,
             0.25 * get_frame_time() as f64 * 0.25,
         ));
+        camera.project(floor.clone());
         camera.project(cube.clone());
-        let tris = camera.sorted();
-        // println!("{} tris", tris.len());
-        for tri in tris {
-            let p = tri.points();
-            let p1 = Vec2::new(p[0].x() as f32, p[0].y() as f32);
-            let p2 = Vec2::new(p[1].x() as f32, p[1].y() as f32);
-            let p3 = Vec2::new(p[2].x() as f32, p[2].y() as f32);
-            draw_triangle(p1, p2, p3, tri.color());
-            draw_triangle_lines(p1, p2, p3, 1.5, BLACK)
+        let mode = camera.render_mode();
+        for batch in camera.batches(false) {
+            for tri in batch.triangles {
+                if mode.fills() {
+                    let p = tri.points();
+                    let p1 = Vec2::new(p[0].x() as f32, p[0].y() as f32);
+                    let p2 = Vec2::new(p[1].x() as f32, p[1].y() as f32);
+                    let p3 = Vec2::new(p[2].x() as f32, p[2].y() as f32);
+                    draw_triangle(p1, p2, p3, batch.color);
+                }
+                if mode.draws_edges() {
+                    for (a, b) in tri.edges() {
+                        draw_line(a.x() as f32, a.y() as f32, b.x() as f32, b.y() as f32, 1.5, BLACK);
+                    }
+                }
+            }
         }
         camera.clear();
         next_frame().await;
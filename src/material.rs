@@ -0,0 +1,228 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use macroquad::prelude::Color;
+
+use crate::math::vec_3d::Vec3d;
+use crate::object::Mesh;
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    color: Color,
+    reflectivity: f64,
+    texture: Option<Arc<Texture>>,
+}
+
+impl Material {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            reflectivity: 0.0,
+            texture: None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+    pub fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+
+    // 0.0 - plain color, 1.0 - perfect mirror of the camera's environment
+    pub fn reflectivity(&self) -> f64 {
+        self.reflectivity
+    }
+    pub fn reflectivity_mut(&mut self) -> &mut f64 {
+        &mut self.reflectivity
+    }
+
+    // applied to triangles with uvs, modulated by the shaded color
+    pub fn texture(&self) -> Option<&Arc<Texture>> {
+        self.texture.as_ref()
+    }
+    pub fn texture_mut(&mut self) -> &mut Option<Arc<Texture>> {
+        &mut self.texture
+    }
+}
+
+// Image sampled by texture coordinates, u to the right and v down, both in [0, 1].
+#[derive(Debug, Clone)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+}
+
+impl Texture {
+    // texels are row major, top row first
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> Self {
+        assert_eq!(texels.len(), width * height, "Texture: expected width * height texels");
+        Self { width, height, texels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // nearest texel, coordinates outside [0, 1] are clamped to the border
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.texels[y * self.width + x]
+    }
+}
+
+// Environment made of six flat colored faces, in +X, -X, +Y, -Y, +Z, -Z order.
+#[derive(Debug, Clone)]
+pub struct CubeMap {
+    faces: [Color; 6],
+}
+
+impl CubeMap {
+    pub fn new(faces: [Color; 6]) -> Self {
+        Self { faces }
+    }
+
+    pub fn faces(&self) -> &[Color; 6] {
+        &self.faces
+    }
+
+    // color of the face the direction points to
+    pub fn sample(&self, dir: &Vec3d) -> Color {
+        let (x, y, z) = (dir.x().abs(), dir.y().abs(), dir.z().abs());
+        let face = if x >= y && x >= z {
+            if dir.x() >= 0.0 {
+                0
+            } else {
+                1
+            }
+        } else if y >= z {
+            if dir.y() >= 0.0 {
+                2
+            } else {
+                3
+            }
+        } else if dir.z() >= 0.0 {
+            4
+        } else {
+            5
+        };
+        self.faces[face]
+    }
+}
+
+pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    Color::new(
+        a.r + (b.r - a.r) * t,
+        a.g + (b.g - a.g) * t,
+        a.b + (b.b - a.b) * t,
+        a.a + (b.a - a.a) * t,
+    )
+}
+
+// Rounds each color channel (not alpha) to one of levels evenly spaced values in [0, 1].
+// Less than 2 levels leave the color as it is.
+pub fn posterize(color: Color, levels: u8) -> Color {
+    if levels < 2 {
+        return color;
+    }
+
+    let steps = (levels - 1) as f32;
+    let snap = |c: f32| (c.clamp(0.0, 1.0) * steps).round() / steps;
+    Color::new(snap(color.r), snap(color.g), snap(color.b), color.a)
+}
+
+// Pulses a color between two endpoints on a sine wave: `from` at time 0, `to` half a period later.
+#[derive(Debug, Clone)]
+pub struct MaterialAnimator {
+    from: Color,
+    to: Color,
+    frequency: f64,
+}
+
+impl MaterialAnimator {
+    pub fn new(from: Color, to: Color, frequency: f64) -> Self {
+        Self { from, to, frequency }
+    }
+
+    pub fn sample(&self, time: f64) -> Color {
+        let t = 0.5 - 0.5 * (2.0 * PI * self.frequency * time).cos();
+        lerp_color(self.from, self.to, t as f32)
+    }
+
+    // Recolors the mesh and its triangles without a material. Triangles with a material (e.g. the faces of
+    // Cube::new_colored) keep their color, use apply_material to pulse a material.
+    pub fn apply(&self, mesh: &mut dyn Mesh, time: f64) {
+        let color = self.sample(time);
+        *mesh.color_mut() = color;
+        for tri in mesh.triangles_mut().iter_mut() {
+            if tri.material_id().is_none() {
+                *tri.color_mut() = color;
+            }
+        }
+    }
+
+    pub fn apply_material(&self, material: &mut Material, time: f64) {
+        *material.color_mut() = self.sample(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::Color;
+
+    use crate::object::{Cube, Mesh, ObjectNameTag};
+
+    use super::{Material, MaterialAnimator};
+
+    #[test]
+    fn animator_extremes() {
+        let from = Color::new(1.0, 0.0, 0.0, 1.0);
+        let to = Color::new(0.0, 0.0, 1.0, 1.0);
+        let animator = MaterialAnimator::new(from, to, 2.0);
+
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, from);
+
+        animator.apply(&mut cube, 0.25);
+        assert_eq!(cube.color(), to);
+        assert!(cube.triangles().iter().all(|t| t.color() == to));
+
+        animator.apply(&mut cube, 0.5);
+        assert_eq!(cube.color(), from);
+
+        let mid = animator.sample(0.125);
+        assert!((mid.r - 0.5).abs() < 1e-6 && (mid.b - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn animator_keeps_own_colors() {
+        let from = Color::new(1.0, 0.0, 0.0, 1.0);
+        let to = Color::new(0.0, 0.0, 1.0, 1.0);
+        let animator = MaterialAnimator::new(from, to, 2.0);
+
+        // one face has the color the animator reaches, the mesh color passes through it
+        let mut faces = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6].map(|g| Color::new(0.0, g, 0.0, 1.0));
+        faces[0] = to;
+        let mut colored = Cube::new_colored(ObjectNameTag::new("Colored"), 1.0, faces);
+        let before: Vec<Color> = colored.triangles().iter().map(|t| colored.material_color(t)).collect();
+        for time in [0.25, 0.5, 0.75] {
+            animator.apply(&mut colored, time);
+            assert_eq!(colored.color(), animator.sample(time));
+            assert!(colored.triangles().iter().zip(&before).all(|(t, c)| colored.material_color(t) == *c));
+        }
+
+        // triangles with a material pulse through it
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, from);
+        *cube.materials_mut() = vec![Material::new(from)];
+        *cube.triangles_mut()[0].material_id_mut() = Some(0);
+        animator.apply(&mut cube, 0.25);
+        assert_eq!(cube.triangles()[0].color(), from);
+        animator.apply_material(&mut cube.materials_mut()[0], 0.25);
+        assert_eq!(cube.material_color(&cube.triangles()[0]), to);
+    }
+}
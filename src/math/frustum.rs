@@ -0,0 +1,34 @@
+use super::plane::Plane;
+use super::vec_3d::Vec3d;
+
+// Convex volume bounded by planes, inside is where every plane's distance is non-negative.
+pub struct Frustum {
+    planes: Vec<Plane>,
+}
+
+impl Frustum {
+    pub fn new(planes: Vec<Plane>) -> Self {
+        Self { planes }
+    }
+
+    pub fn planes(&self) -> &Vec<Plane> {
+        &self.planes
+    }
+
+    pub fn contains_point(&self, point: &Vec3d) -> bool {
+        self.planes.iter().all(|plane| plane.distance(point) >= 0.0)
+    }
+
+    // Conservative box test: false only when the box is entirely behind one of the planes.
+    pub fn intersects_aabb(&self, min: &Vec3d, max: &Vec3d) -> bool {
+        self.planes.iter().all(|plane| {
+            let n = plane.normal();
+            let farthest = Vec3d::new(
+                if n.x() >= 0.0 { max.x() } else { min.x() },
+                if n.y() >= 0.0 { max.y() } else { min.y() },
+                if n.z() >= 0.0 { max.z() } else { min.z() },
+            );
+            plane.distance(&farthest) >= 0.0
+        })
+    }
+}
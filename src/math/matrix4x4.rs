@@ -1,7 +1,6 @@
 use std::f64::consts::PI;
 
-use crate::math::is_near;
-
+use super::is_near;
 use super::vec_3d::Vec3d;
 use super::vec_4d::Vec4d;
 
@@ -40,6 +39,7 @@ impl Matrix4x4 {
         res
     }
 
+    #[cfg(test)]
     fn constant(v: f64) -> Matrix4x4 {
         Matrix4x4([[v; 4]; 4])
     }
@@ -146,6 +146,30 @@ impl Matrix4x4 {
         res
     }
 
+    // Column major flat array (element [row][col] at col * 4 + row) as OpenGL / wgpu and glam expect,
+    // so a translation ends up in elements 12, 13 and 14.
+    pub fn to_array(&self) -> [f64; 16] {
+        std::array::from_fn(|i| self.0[i % 4][i / 4])
+    }
+
+    // to_array converted to f32
+    pub fn to_array_f32(&self) -> [f32; 16] {
+        self.to_array().map(|v| v as f32)
+    }
+
+    // inverse of to_array, the array is column major
+    pub fn from_array(values: [f64; 16]) -> Matrix4x4 {
+        Matrix4x4(std::array::from_fn(|row| std::array::from_fn(|col| values[col * 4 + row])))
+    }
+
+    // projection with separate horizontal and vertical fov (in degrees), for non-square pixels
+    pub fn projection_aniso(fov_x: f64, fov_y: f64, znear: f64, zfar: f64) -> Matrix4x4 {
+        let mut res = Matrix4x4::projection(fov_y, 1.0, znear, zfar);
+        res.0[0][0] = 1. / (PI * fov_x * 0.5 / 180.0).tan();
+
+        res
+    }
+
     pub fn screen_space(width: i32, height: i32) -> Matrix4x4 {
         let mut res = Matrix4x4::identity();
         res.0[0][0] = -0.5 * width as f64;
@@ -193,6 +217,113 @@ impl Matrix4x4 {
     }
 }
 
+impl Matrix4x4 {
+    pub fn determinant(&self) -> f64 {
+        let adjugate = self.cofactors();
+        (0..4).map(|j| self.0[0][j] * adjugate[j][0]).sum()
+    }
+
+    // cofactor matrix, stored transposed (the adjugate): res[j][i] is the cofactor of element [i][j]
+    fn cofactors(&self) -> [[f64; 4]; 4] {
+        let m = &self.0;
+        let cofactor = |i: usize, j: usize| {
+            let r: Vec<usize> = (0..4).filter(|&r| r != i).collect();
+            let c: Vec<usize> = (0..4).filter(|&c| c != j).collect();
+            let minor = m[r[0]][c[0]] * (m[r[1]][c[1]] * m[r[2]][c[2]] - m[r[1]][c[2]] * m[r[2]][c[1]])
+                - m[r[0]][c[1]] * (m[r[1]][c[0]] * m[r[2]][c[2]] - m[r[1]][c[2]] * m[r[2]][c[0]])
+                + m[r[0]][c[2]] * (m[r[1]][c[0]] * m[r[2]][c[1]] - m[r[1]][c[1]] * m[r[2]][c[0]]);
+            if (i + j).is_multiple_of(2) { minor } else { -minor }
+        };
+
+        std::array::from_fn(|j| std::array::from_fn(|i| cofactor(i, j)))
+    }
+
+    // true if the linear (upper left 3x3) part flips orientation, like a negative scale does
+    pub fn is_mirroring(&self) -> bool {
+        let m = &self.0;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        det < 0.0
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        Matrix4x4(std::array::from_fn(|i| std::array::from_fn(|j| self.0[j][i])))
+    }
+
+    // Inverse-transpose of the upper left 3x3 (no translation), for transforming normals: they stay
+    // perpendicular to the surface under non-uniform scale and shear. Normalize the results.
+    // A singular 3x3 gives its cofactor matrix, which still maps normals of the faces it doesn't flatten.
+    pub fn normal_matrix(&self) -> Matrix4x4 {
+        let m = &self.0;
+        let cofactor = |i: usize, j: usize| {
+            let (i1, i2, j1, j2) = ((i + 1) % 3, (i + 2) % 3, (j + 1) % 3, (j + 2) % 3);
+            m[i1][j1] * m[i2][j2] - m[i1][j2] * m[i2][j1]
+        };
+        let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+        let scale = if is_near(det, 0.0) { 1.0 } else { 1.0 / det };
+
+        let mut res = Matrix4x4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.0[i][j] = cofactor(i, j) * scale;
+            }
+        }
+
+        res
+    }
+
+    // Gram-Schmidt on the x, y, z basis columns: unit length and mutually orthogonal again,
+    // keeping the direction of x and the handedness. Translation is kept, scale is dropped.
+    pub fn orthonormalized(&self) -> Matrix4x4 {
+        let x = self.x().normalized();
+        let y = (self.y() - x * x.dot(&self.y())).normalized();
+        let z = (self.z() - x * x.dot(&self.z()) - y * y.dot(&self.z())).normalized();
+
+        let mut res = self.clone();
+        for (j, axis) in [x, y, z].iter().enumerate() {
+            res.0[0][j] = axis.x();
+            res.0[1][j] = axis.y();
+            res.0[2][j] = axis.z();
+        }
+
+        res
+    }
+
+    // None for singular matrices
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let adjugate = self.cofactors();
+        let det: f64 = (0..4).map(|j| self.0[0][j] * adjugate[j][0]).sum();
+
+        if is_near(det, 0.0) {
+            return None;
+        }
+
+        Some(Matrix4x4(adjugate.map(|row| row.map(|v| v / det))))
+    }
+
+    // The transform applied n times, negative n applies the inverse.
+    // Panics for negative n if the matrix is singular.
+    pub fn powi(&self, n: i32) -> Matrix4x4 {
+        let mut base = if n < 0 {
+            self.inverse().expect("Matrix4x4::powi: singular matrix has no negative powers")
+        } else {
+            self.clone()
+        };
+        let mut exp = n.unsigned_abs();
+        let mut res = Matrix4x4::identity();
+
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = res * base.clone();
+            }
+            base = base.clone() * base;
+            exp >>= 1;
+        }
+
+        res
+    }
+}
+
 impl std::ops::Mul<Matrix4x4> for Matrix4x4 {
     type Output = Matrix4x4;
 
@@ -215,6 +346,24 @@ impl std::ops::Mul<Vec4d> for Matrix4x4 {
     type Output = Vec4d;
 
     fn mul(self, rhs: Vec4d) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl std::ops::Mul<Vec3d> for Matrix4x4 {
+    type Output = Vec3d;
+
+    fn mul(self, rhs: Vec3d) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+// borrow
+
+impl std::ops::Mul<&Vec4d> for &Matrix4x4 {
+    type Output = Vec4d;
+
+    fn mul(self, rhs: &Vec4d) -> Self::Output {
         Vec4d::new(
             self.0[0][0] * rhs.x() + self.0[0][1] * rhs.y() + self.0[0][2] * rhs.z() + self.0[0][3] * rhs.w(),
             self.0[1][0] * rhs.x() + self.0[1][1] * rhs.y() + self.0[1][2] * rhs.z() + self.0[1][3] * rhs.w(),
@@ -224,10 +373,10 @@ impl std::ops::Mul<Vec4d> for Matrix4x4 {
     }
 }
 
-impl std::ops::Mul<Vec3d> for Matrix4x4 {
+impl std::ops::Mul<&Vec3d> for &Matrix4x4 {
     type Output = Vec3d;
 
-    fn mul(self, rhs: Vec3d) -> Self::Output {
+    fn mul(self, rhs: &Vec3d) -> Self::Output {
         Vec3d::new(
             self.0[0][0] * rhs.x() + self.0[0][1] * rhs.y() + self.0[0][2] * rhs.z(),
             self.0[1][0] * rhs.x() + self.0[1][1] * rhs.y() + self.0[1][2] * rhs.z(),
@@ -236,31 +385,48 @@ impl std::ops::Mul<Vec3d> for Matrix4x4 {
     }
 }
 
+impl PartialEq<Matrix4x4> for Matrix4x4 {
+    fn eq(&self, other: &Matrix4x4) -> bool {
+        let mut diff = 0.0;
+        for i in 0..4 {
+            for j in 0..4 {
+                diff += (self.0[i][j] - other.0[i][j]) * (self.0[i][j] - other.0[i][j]);
+            }
+        }
+
+        diff < f64::EPSILON
+    }
+}
+
 #[cfg(test)]
-mod Tests {
+mod tests {
     use crate::math::is_near;
     use crate::math::vec_3d::Vec3d;
     use crate::math::vec_4d::Vec4d;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
     use std::f64::consts::PI;
 
     use super::Matrix4x4;
 
+    #[test]
     fn matrix_vec_mul() {
         let v = Vec4d::new(4., 2., 3., 1.);
 
-        let one = Matrix4x4::identity() * v.clone();
+        let one = Matrix4x4::identity() * v;
         assert!(is_near(one.x(), 4.) && is_near(one.y(), 2.) && is_near(one.z(), 3.,) && is_near(one.w(), 1.));
 
-        let scale = Matrix4x4::scale(&Vec3d::new(1., 2., 3.)) * v.clone();
+        let scale = Matrix4x4::scale(&Vec3d::new(1., 2., 3.)) * v;
         assert!(is_near(scale.x(), 4.) && is_near(scale.y(), 4.) && is_near(scale.z(), 9.,) && is_near(scale.w(), 1.));
 
-        let zero = Matrix4x4::zero() * v.clone();
+        let zero = Matrix4x4::zero() * v;
         assert!(is_near(zero.x(), 0.) && is_near(zero.y(), 0.) && is_near(zero.z(), 0.) && is_near(zero.w(), 0.));
 
         let trans = Matrix4x4::translation(&Vec3d::new(5., 4., 3.)) * v;
         assert!(is_near(trans.x(), 9.) && is_near(trans.y(), 6.) && is_near(trans.z(), 6.) && is_near(trans.w(), 1.));
     }
 
+    #[test]
     fn matrix_matrix_mul() {
         let c1 = Matrix4x4::constant(5.) * Matrix4x4::identity();
         for i in 0..4 {
@@ -292,33 +458,143 @@ mod Tests {
         }
     }
 
+    #[test]
     fn rotation() {
         let i = Vec4d::new(1., 0., 0., 0.);
         let j = Vec4d::new(0., 1., 0., 0.);
         let k = Vec4d::new(0., 0., 1., 0.);
 
-        let i_rx = Matrix4x4::rotation_x(PI / 2.) * i.clone();
-        let j_rx = Matrix4x4::rotation_x(PI / 2.) * j.clone();
-        let k_rx = Matrix4x4::rotation_x(PI / 2.) * k.clone();
+        let i_rx = Matrix4x4::rotation_x(PI / 2.) * i;
+        let j_rx = Matrix4x4::rotation_x(PI / 2.) * j;
+        let k_rx = Matrix4x4::rotation_x(PI / 2.) * k;
 
         assert_eq!(i_rx, i);
         assert_eq!(j_rx, k);
-        assert_eq!(k_rx, -j.clone());
+        assert_eq!(k_rx, -j);
 
-        let i_ry = Matrix4x4::rotation_y(PI / 2.) * i.clone();
-        let j_ry = Matrix4x4::rotation_y(PI / 2.) * j.clone();
-        let k_ry = Matrix4x4::rotation_y(PI / 2.) * k.clone();
+        let i_ry = Matrix4x4::rotation_y(PI / 2.) * i;
+        let j_ry = Matrix4x4::rotation_y(PI / 2.) * j;
+        let k_ry = Matrix4x4::rotation_y(PI / 2.) * k;
 
-        assert_eq!(i_ry, -k.clone());
+        assert_eq!(i_ry, -k);
         assert_eq!(j_ry, j);
         assert_eq!(k_ry, i);
 
-        let i_rz = Matrix4x4::rotation_z(PI / 2.) * i.clone();
-        let j_rz = Matrix4x4::rotation_z(PI / 2.) * j.clone();
-        let k_rz = Matrix4x4::rotation_z(PI / 2.) * k.clone();
+        let i_rz = Matrix4x4::rotation_z(PI / 2.) * i;
+        let j_rz = Matrix4x4::rotation_z(PI / 2.) * j;
+        let k_rz = Matrix4x4::rotation_z(PI / 2.) * k;
 
         assert_eq!(i_rz, j);
         assert_eq!(j_rz, -i);
         assert_eq!(k_rz, k);
     }
+
+    #[test]
+    fn inverse() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut random_vec = |min: f64, max: f64| {
+            Vec3d::new(rng.gen_range(min..max), rng.gen_range(min..max), rng.gen_range(min..max))
+        };
+
+        for _ in 0..20 {
+            let m = Matrix4x4::translation(&random_vec(-10.0, 10.0))
+                * Matrix4x4::rotation(&random_vec(-PI, PI))
+                * Matrix4x4::scale(&random_vec(0.2, 3.0));
+            let inverse = m.inverse().unwrap();
+            assert!((m.determinant() * inverse.determinant() - 1.0).abs() < 1e-9);
+
+            let identity = Matrix4x4::identity().to_array();
+            for product in [m.clone() * inverse.clone(), inverse * m] {
+                for (a, b) in product.to_array().iter().zip(identity.iter()) {
+                    assert!((a - b).abs() < 1e-9);
+                }
+            }
+        }
+
+        let flat = Matrix4x4::scale(&Vec3d::new(1.0, 0.0, 2.0));
+        assert!(is_near(flat.determinant(), 0.0));
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn normal_matrix() {
+        let m = Matrix4x4::translation(&Vec3d::new(1., 2., 3.))
+            * Matrix4x4::rotation(&Vec3d::new(0.3, -0.7, 1.1))
+            * Matrix4x4::scale(&Vec3d::new(2., 0.5, 3.));
+        assert_eq!(m.transpose().transpose(), m);
+        assert!((m.transpose().determinant() - m.determinant()).abs() < 1e-12);
+
+        let mut linear = m.clone();
+        linear.0[0][3] = 0.;
+        linear.0[1][3] = 0.;
+        linear.0[2][3] = 0.;
+        assert_eq!(m.normal_matrix(), linear.inverse().unwrap().transpose());
+
+        // normal of the plane x + y = 0 after stretching x
+        let stretch = Matrix4x4::scale(&Vec3d::new(4., 1., 1.));
+        let (tangent, normal) = (Vec3d::new(1., -1., 0.), Vec3d::new(1., 1., 0.));
+        assert!((&stretch * &tangent).dot(&(&stretch.normal_matrix() * &normal)).abs() < 1e-12);
+        assert!((&stretch * &tangent).dot(&(&stretch * &normal)).abs() > 1.);
+    }
+
+    #[test]
+    fn powi() {
+        let r = Matrix4x4::rotation(&Vec3d::new(0., PI / 2., 0.));
+        assert_eq!(r.powi(4), Matrix4x4::identity());
+        assert_eq!(r.powi(0), Matrix4x4::identity());
+        assert_eq!(r.powi(2), r.clone() * r.clone());
+        assert_eq!(r.powi(-1) * r.clone(), Matrix4x4::identity());
+
+        let t = Matrix4x4::translation(&Vec3d::new(1., 2., 3.));
+        assert_eq!(t.powi(5), Matrix4x4::translation(&Vec3d::new(5., 10., 15.)));
+        assert_eq!(t.powi(-3), Matrix4x4::translation(&Vec3d::new(-3., -6., -9.)));
+    }
+
+    #[test]
+    fn projection_aniso() {
+        assert_eq!(Matrix4x4::projection_aniso(90., 90., 0.1, 100.), Matrix4x4::projection(90., 1., 0.1, 100.));
+
+        let point = Vec4d::new(1., 1., 5., 1.);
+        let square = Matrix4x4::projection(90., 1., 0.1, 100.) * point;
+        let aniso = Matrix4x4::projection_aniso(90., 60., 0.1, 100.) * point;
+        assert!(is_near(aniso.x(), square.x()));
+        assert!((aniso.y() * (PI / 6.).tan() - square.y()).abs() < 1e-12);
+        assert!(is_near(aniso.z(), square.z()) && is_near(aniso.w(), square.w()));
+    }
+
+    #[test]
+    fn orthonormalized() {
+        let mut m = Matrix4x4::translation(&Vec3d::new(1.0, 2.0, 3.0)) * Matrix4x4::rotation(&Vec3d::new(0.3, -0.7, 1.1));
+        let rotation = m.clone();
+        // skew and stretch the basis a little
+        m.0[0][1] += 0.01;
+        m.0[2][0] -= 0.02;
+        m.0[1][2] *= 1.03;
+
+        let o = m.orthonormalized();
+        for (a, b) in [(o.x(), o.y()), (o.y(), o.z()), (o.z(), o.x())] {
+            assert!((a.abs() - 1.0).abs() < 1e-12);
+            assert!(a.dot(&b).abs() < 1e-12);
+        }
+        assert_eq!(o.w(), Vec3d::new(1.0, 2.0, 3.0));
+        assert!(o.x().dot(&rotation.x()) > 0.999 && o.z().dot(&rotation.z()) > 0.999);
+        assert!(!o.is_mirroring());
+        assert_eq!(rotation.orthonormalized(), rotation);
+    }
+
+    #[test]
+    fn flat_array_layout() {
+        let m = Matrix4x4::translation(&Vec3d::new(1.0, 2.0, 3.0)) * Matrix4x4::rotation(&Vec3d::new(0.3, -0.7, 1.1));
+        assert_eq!(Matrix4x4::from_array(m.to_array()), m);
+
+        let t = Matrix4x4::translation(&Vec3d::new(1.0, 2.0, 3.0)).to_array_f32();
+        #[rustfmt::skip]
+        let expected = [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            1.0, 2.0, 3.0, 1.0,
+        ];
+        assert_eq!(t, expected);
+    }
 }
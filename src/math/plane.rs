@@ -5,6 +5,14 @@ use super::vec_3d::Vec3d;
 pub struct Plane {
     normal: Vec3d,
     point: Vec3d,
+    // point . normal, so distance is a single dot product
+    offset: f64,
+}
+
+// vertex distances evaluated by clip_into, counted for the tests
+#[cfg(test)]
+thread_local! {
+    static CLIP_DISTANCE_EVALS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
 }
 
 impl Plane {
@@ -17,21 +25,26 @@ impl Plane {
     }
 
     pub fn new(normal: Vec3d, point: Vec3d) -> Self {
+        let normal = normal.normalized();
         Self {
-            normal: normal.normalized(),
+            offset: point.dot(&normal),
+            normal,
             point,
         }
     }
 
     pub fn from_triangle(triangle: &Triangle) -> Self {
+        let normal = triangle.normal();
+        let point = Vec3d::from_vec4d(&triangle.points()[0]);
         Self {
-            normal: triangle.normal(),
-            point: Vec3d::from_vec4d(&triangle.points()[0]),
+            offset: point.dot(&normal),
+            normal,
+            point,
         }
     }
 
     pub fn distance(&self, point: &Vec3d) -> f64 {
-        point.dot(&self.normal) - self.point.dot(&self.normal)
+        point.dot(&self.normal) - self.offset
     }
 
     pub fn intersection(&self, start: &Vec3d, end: &Vec3d) -> (Vec3d, f64) {
@@ -43,59 +56,184 @@ impl Plane {
 
     pub fn clip(&self, tri: &Triangle) -> Vec<Triangle> {
         let mut res = Vec::new();
+        self.clip_into(tri, &mut res);
+        res
+    }
+
+    // same as clip, appends the parts of the triangle in front of the plane to out without allocating.
+    // Cut pieces that collapse (a vertex just touching the plane) are dropped.
+    pub fn clip_into(&self, tri: &Triangle, out: &mut Vec<Triangle>) {
+        // one distance per vertex, the intersection parameters are derived from them
+        let distances = tri.points().map(|p| {
+            #[cfg(test)]
+            CLIP_DISTANCE_EVALS.with(|evals| evals.set(evals.get() + 1));
+            self.distance(&Vec3d::from_vec4d(&p))
+        });
+        let k = |i: usize, o: usize| distances[i] / (distances[i] - distances[o]);
+        let mut push = |piece: Triangle| {
+            if !piece.is_degenerate() {
+                out.push(piece);
+            }
+        };
+
+        let (mut inside_points, mut inside_count) = ([0; 3], 0);
+        let (mut outside_points, mut outside_count) = ([0; 3], 0);
+        for (i, distance) in distances.iter().enumerate() {
+            if *distance >= 0.0 {
+                inside_points[inside_count] = i;
+                inside_count += 1;
+            } else {
+                outside_points[outside_count] = i;
+                outside_count += 1;
+            }
+        }
+
+        if inside_count == 1 {
+            let (i0, o0, o1) = (inside_points[0], outside_points[0], outside_points[1]);
+            let k1 = k(i0, o0);
+            let k2 = k(i0, o1);
+
+            push(tri.lerp_vertices([(i0, i0, 0.0), (i0, o0, k1), (i0, o1, k2)]));
+        }
+
+        if inside_count == 2 {
+            let (i0, i1, o0) = (inside_points[0], inside_points[1], outside_points[0]);
+            let k1 = k(i0, o0);
+            let k2 = k(i1, o0);
+
+            push(tri.lerp_vertices([(i0, i0, 0.0), (i0, o0, k1), (i1, i1, 0.0)]));
+            push(tri.lerp_vertices([(i0, o0, k1), (i1, o0, k2), (i1, i1, 0.0)]));
+        }
 
-        let mut inside_points = vec![];
-        let mut outside_points = vec![];
+        if inside_count == 3 {
+            out.push(tri.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::WHITE;
 
-        let distances = [
-            self.distance(&Vec3d::from_vec4d(&tri.points()[0])),
-            self.distance(&Vec3d::from_vec4d(&tri.points()[1])),
-            self.distance(&Vec3d::from_vec4d(&tri.points()[2])),
+    use crate::math::vec_3d::Vec3d;
+    use crate::math::vec_4d::Vec4d;
+    use crate::triangle::Triangle;
+
+    use super::{Plane, CLIP_DISTANCE_EVALS};
+
+    #[test]
+    fn clip_keeps_parent_normal() {
+        let plane = Plane::new(Vec3d::new(0.0, 0.0, 1.0), Vec3d::new(0.0, 0.0, 1.0));
+        let points = [
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 1.0, 2.0, 1.0),
+            Vec4d::new(1.0, 0.0, 3.0, 1.0),
         ];
 
+        // every rotation of the vertex order, so each vertex gets to be the outside one
         for i in 0..3 {
-            if distances[i] >= 0.0 {
-                inside_points.push(Vec3d::from_vec4d(&tri.points()[i]));
-            } else {
-                outside_points.push(Vec3d::from_vec4d(&tri.points()[i]));
+            let tri = Triangle::new(
+                WHITE,
+                points[i],
+                points[(i + 1) % 3],
+                points[(i + 2) % 3],
+            );
+            let fragments = plane.clip(&tri);
+            assert_eq!(fragments.len(), 2);
+            for fragment in fragments {
+                assert_eq!(fragment.normal(), tri.normal());
             }
         }
 
-        if inside_points.len() == 1 {
-            let intersect1 = self.intersection(&inside_points[0], &outside_points[0]);
-            let intersect2 = self.intersection(&inside_points[0], &outside_points[1]);
-
-            res.push(Triangle::new(
-                tri.color(),
-                inside_points[0].make_point_4d(),
-                intersect1.0.make_point_4d(),
-                intersect2.0.make_point_4d(),
-            ));
+        let tri = Triangle::new(WHITE, points[1], points[0], points[2]);
+        let fragments = plane.clip(&tri);
+        assert_eq!(fragments.len(), 2);
+        for fragment in fragments {
+            assert_eq!(fragment.normal(), tri.normal());
         }
+    }
 
-        if inside_points.len() == 2 {
-            let intersect1 = self.intersection(&inside_points[0], &outside_points[0]);
-            let intersect2 = self.intersection(&inside_points[1], &outside_points[0]);
-
-            res.push(Triangle::new(
-                tri.color(),
-                inside_points[0].make_point_4d(),
-                intersect1.0.make_point_4d(),
-                inside_points[1].make_point_4d(),
-            ));
-
-            res.push(Triangle::new(
-                tri.color(),
-                intersect1.0.make_point_4d(),
-                intersect2.0.make_point_4d(),
-                inside_points[1].make_point_4d(),
-            ));
-        }
+    #[test]
+    fn clip_drops_collapsed_pieces() {
+        let plane = Plane::new(Vec3d::new(0.0, 0.0, 1.0), Vec3d::new(0.0, 0.0, 1.0));
+        // only the first vertex is on the plane, the rest behind it
+        let touching = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 1.0, 1.0),
+            Vec4d::new(0.0, 1.0, 0.0, 1.0),
+            Vec4d::new(1.0, 0.0, -1.0, 1.0),
+        );
+        assert!(plane.clip(&touching).is_empty());
+
+        // one vertex on the plane, one on each side: a single piece is left
+        let across = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 1.0, 1.0),
+            Vec4d::new(0.0, 1.0, 0.0, 1.0),
+            Vec4d::new(1.0, 0.0, 2.0, 1.0),
+        );
+        let pieces = plane.clip(&across);
+        assert_eq!(pieces.len(), 1);
+        assert!(!pieces[0].is_degenerate());
+    }
+
+    #[test]
+    fn clip_matches_edge_intersections() {
+        let plane = Plane::new(Vec3d::new(1.0, 2.0, 2.0).normalized(), Vec3d::new(0.5, 0.0, 0.0));
+        let (a, b, c) = (Vec3d::new(2.0, 1.0, 0.5), Vec3d::new(-2.0, -1.0, 0.0), Vec3d::new(-1.0, -2.0, -1.0));
+        assert!(plane.distance(&a) > 0.0 && plane.distance(&b) < 0.0 && plane.distance(&c) < 0.0);
+        let tri = Triangle::new(WHITE, a.make_point_4d(), b.make_point_4d(), c.make_point_4d());
+
+        // the cut points derived from the vertex distances are where the edges cross the plane
+        let cut = |plane: &Plane, p: &Vec3d, q: &Vec3d| plane.intersection(p, q).0.make_point_4d();
+        let one = plane.clip(&tri);
+        assert_eq!(one.len(), 1);
+        assert_eq!(*one[0].points(), [a.make_point_4d(), cut(&plane, &a, &b), cut(&plane, &a, &c)]);
+
+        // seen from the other side two vertices are in front, the quad left is split in two
+        let flipped = Plane::new(-plane.normal(), *plane.point());
+        let two = flipped.clip(&tri);
+        assert_eq!(two.len(), 2);
+        let (ba, ca) = (cut(&flipped, &b, &a), cut(&flipped, &c, &a));
+        assert_eq!(*two[0].points(), [b.make_point_4d(), ba, c.make_point_4d()]);
+        assert_eq!(*two[1].points(), [ba, ca, c.make_point_4d()]);
+    }
 
-        if inside_points.len() == 3 {
-            res.push(tri.clone());
+    #[test]
+    fn clip_evaluates_each_vertex_once() {
+        // box around the origin, the triangle pokes out of it on every side
+        let mut planes = vec![];
+        for axis in [Vec3d::new(1.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 0.0, 1.0)] {
+            planes.push(Plane::new(axis, axis * -1.0));
+            planes.push(Plane::new(-axis, axis));
+        }
+        let tri = Triangle::new(
+            WHITE,
+            Vec4d::new(-3.0, -2.0, -0.5, 1.0),
+            Vec4d::new(3.0, -2.5, 0.5, 1.0),
+            Vec4d::new(0.2, 3.0, 2.5, 1.0),
+        );
+
+        CLIP_DISTANCE_EVALS.with(|evals| evals.set(0));
+        let (mut clipped, mut processed, mut intersections) = (vec![tri], 0, 0);
+        for plane in planes.iter() {
+            let mut next = vec![];
+            for fragment in clipped.iter() {
+                let out = plane.clip(fragment);
+                processed += 1;
+                // a cut fragment has two edges crossing the plane
+                if out.len() == 2 || (out.len() == 1 && out[0].points() != fragment.points()) {
+                    intersections += 2;
+                }
+                next.extend(out);
+            }
+            clipped = next;
         }
+        let evals = CLIP_DISTANCE_EVALS.with(|evals| evals.get());
 
-        res
+        assert!(!clipped.is_empty() && intersections > 0);
+        assert_eq!(evals, 3 * processed);
+        // computing each intersection from scratch needs both endpoint distances again
+        assert!(evals < 3 * processed + 2 * intersections);
     }
 }
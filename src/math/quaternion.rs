@@ -0,0 +1,198 @@
+use super::matrix4x4::Matrix4x4;
+use super::vec_3d::Vec3d;
+
+// x, y, z - vector part, w - scalar part
+#[derive(Debug, Clone)]
+pub struct Quaternion([f64; 4]);
+
+impl Quaternion {
+    pub fn x(&self) -> f64 {
+        self.0[0]
+    }
+    pub fn y(&self) -> f64 {
+        self.0[1]
+    }
+    pub fn z(&self) -> f64 {
+        self.0[2]
+    }
+    pub fn w(&self) -> f64 {
+        self.0[3]
+    }
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Quaternion {
+        Quaternion([x, y, z, w])
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn from_axis_angle(axis: &Vec3d, angle: f64) -> Quaternion {
+        let axis = axis.normalized();
+        let s = (angle * 0.5).sin();
+        Quaternion::new(axis.x() * s, axis.y() * s, axis.z() * s, (angle * 0.5).cos())
+    }
+
+    // rotation part of the matrix, its basis vectors are expected to be orthonormal
+    pub fn from_matrix(m: &Matrix4x4) -> Quaternion {
+        let (c0, c1, c2) = (m.x(), m.y(), m.z());
+        let (m00, m10, m20) = (c0.x(), c0.y(), c0.z());
+        let (m01, m11, m21) = (c1.x(), c1.y(), c1.z());
+        let (m02, m12, m22) = (c2.x(), c2.y(), c2.z());
+
+        let trace = m00 + m11 + m22;
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new((m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s, 0.25 * s)
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            Quaternion::new(0.25 * s, (m01 + m10) / s, (m02 + m20) / s, (m21 - m12) / s)
+        } else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            Quaternion::new((m01 + m10) / s, 0.25 * s, (m12 + m21) / s, (m02 - m20) / s)
+        } else {
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            Quaternion::new((m02 + m20) / s, (m12 + m21) / s, 0.25 * s, (m10 - m01) / s)
+        };
+
+        q.normalized()
+    }
+
+    pub fn sqr_abs(&self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn abs(&self) -> f64 {
+        self.sqr_abs().sqrt()
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let abs = self.abs();
+        if abs > f64::EPSILON {
+            Quaternion::new(self.x() / abs, self.y() / abs, self.z() / abs, self.w() / abs)
+        } else {
+            Quaternion::identity()
+        }
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(-self.x(), -self.y(), -self.z(), self.w())
+    }
+
+    pub fn dot(&self, rhs: &Quaternion) -> f64 {
+        self.x() * rhs.x() + self.y() * rhs.y() + self.z() * rhs.z() + self.w() * rhs.w()
+    }
+
+    // spherical interpolation along the shortest arc
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut dot = self.dot(other);
+        let mut other = other.clone();
+        if dot < 0.0 {
+            other = Quaternion::new(-other.x(), -other.y(), -other.z(), -other.w());
+            dot = -dot;
+        }
+
+        let (k0, k1) = if dot > 0.9995 {
+            // nearly the same rotation, lerp is precise enough and avoids dividing by ~0
+            (1.0 - t, t)
+        } else {
+            let theta = dot.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quaternion::new(
+            self.x() * k0 + other.x() * k1,
+            self.y() * k0 + other.y() * k1,
+            self.z() * k0 + other.z() * k1,
+            self.w() * k0 + other.w() * k1,
+        )
+        .normalized()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4x4 {
+        let q = self.normalized();
+        let axis = Vec3d::new(q.x(), q.y(), q.z());
+        let sin = axis.sqr_abs().sqrt();
+        if sin < f64::EPSILON {
+            return Matrix4x4::identity();
+        }
+
+        Matrix4x4::rotation_around_vec(&axis, 2.0 * sin.atan2(q.w()))
+    }
+
+    pub fn rotate(&self, v: &Vec3d) -> Vec3d {
+        self.to_matrix() * *v
+    }
+}
+
+impl std::ops::Mul<&Quaternion> for &Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: &Quaternion) -> Self::Output {
+        Quaternion::new(
+            self.w() * rhs.x() + self.x() * rhs.w() + self.y() * rhs.z() - self.z() * rhs.y(),
+            self.w() * rhs.y() - self.x() * rhs.z() + self.y() * rhs.w() + self.z() * rhs.x(),
+            self.w() * rhs.z() + self.x() * rhs.y() - self.y() * rhs.x() + self.z() * rhs.w(),
+            self.w() * rhs.w() - self.x() * rhs.x() - self.y() * rhs.y() - self.z() * rhs.z(),
+        )
+    }
+}
+
+impl std::ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl PartialEq<Quaternion> for Quaternion {
+    fn eq(&self, other: &Quaternion) -> bool {
+        let diff = Quaternion::new(
+            self.x() - other.x(),
+            self.y() - other.y(),
+            self.z() - other.z(),
+            self.w() - other.w(),
+        );
+
+        diff.sqr_abs() < f64::EPSILON
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use crate::math::matrix4x4::Matrix4x4;
+    use crate::math::vec_3d::Vec3d;
+
+    use super::Quaternion;
+
+    #[test]
+    fn rotate() {
+        let q = Quaternion::from_axis_angle(&Vec3d::new(0., 0., 1.), PI / 2.);
+        assert_eq!(q.rotate(&Vec3d::new(1., 0., 0.)), Vec3d::new(0., 1., 0.));
+
+        let qq = &q * &q;
+        assert_eq!(qq.rotate(&Vec3d::new(1., 0., 0.)), Vec3d::new(-1., 0., 0.));
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let q = Quaternion::from_axis_angle(&Vec3d::new(1., 2., 3.), 0.7);
+        assert_eq!(Quaternion::from_matrix(&q.to_matrix()), q);
+
+        let m = Matrix4x4::rotation(&Vec3d::new(0.3, -1.2, 2.5));
+        let v = Vec3d::new(1., 2., 3.);
+        assert_eq!(Quaternion::from_matrix(&m).rotate(&v), m * v);
+    }
+
+    #[test]
+    fn slerp() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(&Vec3d::new(0., 1., 0.), PI / 2.);
+        assert_eq!(a.slerp(&b, 0.), a);
+        assert_eq!(a.slerp(&b, 1.), b);
+        assert_eq!(a.slerp(&b, 0.5), Quaternion::from_axis_angle(&Vec3d::new(0., 1., 0.), PI / 4.));
+    }
+}
@@ -1,39 +1,46 @@
-use std::f64::{consts::PI, EPSILON};
+use super::vec_4d::Vec4d;
 
-#[derive(Clone)]
-pub(crate) struct Vec2d {
+#[derive(Clone, Debug)]
+pub struct Vec2d {
     x: f64,
     y: f64,
 }
 
 impl Vec2d {
-    fn new(x: f64, y: f64) -> Vec2d {
+    pub fn new(x: f64, y: f64) -> Vec2d {
         Vec2d { x, y }
     }
 
-    // fn from_vec4d(point4d: &Vec4d) -> Vec2d {
-    //     todo!()
-    // }
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
 
-    
+    // drops z and w, e.g. for screen space points
+    pub fn from_vec4d(point4d: &Vec4d) -> Vec2d {
+        Vec2d::new(point4d.x(), point4d.y())
+    }
 
-    fn sqr_abs(&self) -> f64 {
+    pub fn sqr_abs(&self) -> f64 {
         self.x * self.x + self.y * self.y
     }
 
-    fn abs(&self) -> f64 {
+    pub fn abs(&self) -> f64 {
         self.sqr_abs().sqrt()
     }
 
-    fn normalized(&self) -> Vec2d {
-        if self.abs() > EPSILON {
+    pub fn normalized(&self) -> Vec2d {
+        if self.abs() > f64::EPSILON {
             self.clone() / self.abs()
         } else {
             Vec2d::new(0.0, 0.0)
         }
     }
 
-    fn dot(&self, rhs: &Vec2d) -> f64 {
+    pub fn dot(&self, rhs: &Vec2d) -> f64 {
         self.x * rhs.x + self.y * rhs.y
     }
 }
@@ -74,7 +81,7 @@ impl std::ops::Div<f64> for Vec2d {
     type Output = Vec2d;
 
     fn div(self, rhs: f64) -> Self::Output {
-        if rhs.abs() > EPSILON {
+        if rhs.abs() > f64::EPSILON {
             Vec2d::new(self.x / rhs, self.y / rhs)
         } else {
             panic!("Trying to div by 0")
@@ -87,6 +94,75 @@ impl PartialEq<Vec2d> for Vec2d {
         // self.x == other.x && self.y == other.y
         let diff = self.clone() - other.clone();
 
-        diff.sqr_abs() < EPSILON
+        diff.sqr_abs() < f64::EPSILON
+    }
+}
+
+// borrow
+
+impl std::ops::Neg for &Vec2d {
+    type Output = Vec2d;
+
+    fn neg(self) -> Self::Output {
+        Vec2d::new(-self.x, -self.y)
+    }
+}
+
+impl std::ops::Add<&Vec2d> for &Vec2d {
+    type Output = Vec2d;
+
+    fn add(self, rhs: &Vec2d) -> Self::Output {
+        Vec2d::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl std::ops::Sub<&Vec2d> for &Vec2d {
+    type Output = Vec2d;
+
+    fn sub(self, rhs: &Vec2d) -> Self::Output {
+        Vec2d::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl std::ops::Mul<f64> for &Vec2d {
+    type Output = Vec2d;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Vec2d::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl std::ops::Div<f64> for &Vec2d {
+    type Output = Vec2d;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        if rhs.abs() > f64::EPSILON {
+            Vec2d::new(self.x / rhs, self.y / rhs)
+        } else {
+            panic!("Trying to div by 0")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::vec_4d::Vec4d;
+
+    use super::Vec2d;
+
+    #[test]
+    fn borrowed_ops() {
+        let a = Vec2d::new(3.0, 4.0);
+        let b = Vec2d::from_vec4d(&Vec4d::new(1.0, -2.0, 7.0, 1.0));
+        assert_eq!(b, Vec2d::new(1.0, -2.0));
+
+        assert_eq!(&a + &b, Vec2d::new(4.0, 2.0));
+        assert_eq!(&a - &b, Vec2d::new(2.0, 6.0));
+        assert_eq!(-&a, Vec2d::new(-3.0, -4.0));
+        assert_eq!(&a * 2.0, Vec2d::new(6.0, 8.0));
+        assert_eq!(&a / 2.0, Vec2d::new(1.5, 2.0));
+        assert_eq!(a.abs(), 5.0);
+        assert_eq!(a.normalized(), Vec2d::new(0.6, 0.8));
+        assert_eq!(a.dot(&b), -5.0);
     }
 }
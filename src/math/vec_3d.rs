@@ -1,10 +1,11 @@
-use std::f64::EPSILON;
+use std::f64::consts::PI;
 
 use rand::random;
 
 use super::vec_4d::Vec4d;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec3d([f64; 3]);
 
 impl Vec3d {
@@ -25,13 +26,13 @@ impl Vec3d {
         self.x() * self.x() + self.y() * self.y() + self.z() * self.z()
     }
 
-    fn abs(&self) -> f64 {
+    pub fn abs(&self) -> f64 {
         self.sqr_abs().sqrt()
     }
 
     pub fn normalized(&self) -> Vec3d {
-        if self.abs() > EPSILON {
-            self.clone() / self.abs()
+        if self.abs() > f64::EPSILON {
+            *self / self.abs()
         } else {
             Vec3d::new(0.0, 0.0, 0.0)
         }
@@ -57,15 +58,43 @@ impl Vec3d {
         Vec4d::new(self.x(), self.y(), self.z(), 1.0)
     }
 
-    fn random() -> Vec3d {
-        Vec3d::new(
-            random::<f64>() / f64::MAX,
-            random::<f64>() / f64::MAX,
-            random::<f64>() / f64::MAX,
-        )
+    // components uniform in [0, 1)
+    pub fn random() -> Vec3d {
+        Vec3d::random_range(0.0, 1.0)
+    }
+
+    // components uniform in [min, max)
+    pub fn random_range(min: f64, max: f64) -> Vec3d {
+        let component = || min + (max - min) * random::<f64>();
+        Vec3d::new(component(), component(), component())
+    }
+
+    // uniformly distributed point on the unit sphere (uniform height and angle around the z axis)
+    pub fn random_unit() -> Vec3d {
+        let z = 2.0 * random::<f64>() - 1.0;
+        let phi = 2.0 * PI * random::<f64>();
+        let r = (1.0 - z * z).sqrt();
+        Vec3d::new(r * phi.cos(), r * phi.sin(), z)
     }
 }
 
+// Lattice of counts.0 x counts.1 x counts.2 points starting at origin, step apart along each axis.
+// x changes fastest, then y, then z.
+pub fn grid_points(origin: Vec3d, step: Vec3d, counts: (usize, usize, usize)) -> impl Iterator<Item = Vec3d> {
+    let (nx, ny, nz) = counts;
+    (0..nz).flat_map(move |k| {
+        (0..ny).flat_map(move |j| {
+            (0..nx).map(move |i| {
+                Vec3d::new(
+                    origin.x() + step.x() * i as f64,
+                    origin.y() + step.y() * j as f64,
+                    origin.z() + step.z() * k as f64,
+                )
+            })
+        })
+    })
+}
+
 impl std::ops::Neg for Vec3d {
     type Output = Vec3d;
 
@@ -102,7 +131,7 @@ impl std::ops::Div<f64> for Vec3d {
     type Output = Vec3d;
 
     fn div(self, rhs: f64) -> Self::Output {
-        if rhs.abs() > EPSILON {
+        if rhs.abs() > f64::EPSILON {
             Vec3d::new(self.x() / rhs, self.y() / rhs, self.z() / rhs)
         } else {
             panic!("Trying to div by 0")
@@ -113,9 +142,9 @@ impl std::ops::Div<f64> for Vec3d {
 impl PartialEq<Vec3d> for Vec3d {
     fn eq(&self, other: &Vec3d) -> bool {
         // self.x() == other.x && self.y() == other.y
-        let diff = self.clone() - other.clone();
+        let diff = *self - *other;
 
-        diff.sqr_abs() < EPSILON
+        diff.sqr_abs() < f64::EPSILON
     }
 }
 
@@ -157,7 +186,7 @@ impl std::ops::Div<f64> for &Vec3d {
     type Output = Vec3d;
 
     fn div(self, rhs: f64) -> Self::Output {
-        if rhs.abs() > EPSILON {
+        if rhs.abs() > f64::EPSILON {
             Vec3d::new(self.x() / rhs, self.y() / rhs, self.z() / rhs)
         } else {
             panic!("Trying to div by 0")
@@ -166,22 +195,46 @@ impl std::ops::Div<f64> for &Vec3d {
 }
 
 #[cfg(test)]
-mod Tests {
+mod tests {
     use crate::math::is_near;
 
-    use super::Vec3d;
+    use super::{grid_points, Vec3d};
+
+    #[test]
+    fn random() {
+        for _ in 0..100 {
+            assert!((Vec3d::random_unit().abs() - 1.0).abs() < 1e-12);
+
+            let v = Vec3d::random_range(-2.0, 3.0);
+            assert!([v.x(), v.y(), v.z()].iter().all(|c| (-2.0..3.0).contains(c)));
+        }
+
+        // no longer collapsed to zero: the mean of many samples is near the middle of [0, 1)
+        let n = 2000;
+        let sum = (0..n).fold(Vec3d::new(0.0, 0.0, 0.0), |sum, _| sum + Vec3d::random());
+        assert!((sum / n as f64 - Vec3d::new(0.5, 0.5, 0.5)).abs() < 0.05);
+    }
 
     #[test]
     fn copy() {
         let a = Vec3d::new(1., 2., 3.);
-        let b = Vec3d::new(3., 4., 5.);
-        let c = a.clone();
-        assert!(is_near(c.x(), 1.) && is_near(c.y(), 2.) && is_near(c.z(), 3.))
+        let c = a;
+        assert!(is_near(c.x(), 1.) && is_near(c.y(), 2.) && is_near(c.z(), 3.));
+        // a is still usable after the copy
+        assert_eq!(a, c);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let v = Vec3d::new(1.5, -2., 3.25);
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(json, "[1.5,-2.0,3.25]");
+        assert_eq!(serde_json::from_str::<Vec3d>(&json).unwrap(), v);
     }
 
     #[test]
     fn assign() {
-        let a = Vec3d::new(1., 2., 3.);
         let b = Vec3d::new(3., 4., 5.);
         let c = b;
         assert!(is_near(c.x(), 3.) && is_near(c.y(), 4.) && is_near(c.z(), 5.))
@@ -190,7 +243,6 @@ mod Tests {
     #[test]
     fn neg() {
         let a = Vec3d::new(1., 2., 3.);
-        let b = Vec3d::new(3., 4., 5.);
         let neg = -a;
         assert!(is_near(neg.x(), -1.) && is_near(neg.y(), -2.) && is_near(neg.z(), -3.))
     }
@@ -199,7 +251,7 @@ mod Tests {
     fn plus_minus() {
         let a = Vec3d::new(1., 2., 3.);
         let b = Vec3d::new(3., 4., 5.);
-        let summ = a.clone() + b.clone();
+        let summ = a + b;
         let diff = a - b;
         assert!(is_near(summ.x(), 4.) && is_near(summ.y(), 6.) && is_near(summ.z(), 8.));
         assert!(is_near(diff.x(), -2.) && is_near(diff.y(), -2.) && is_near(diff.z(), -2.));
@@ -209,17 +261,16 @@ mod Tests {
     fn eq() {
         let a = Vec3d::new(1., 2., 3.);
         let b = Vec3d::new(3., 4., 5.);
-        let c = b.clone();
+        let c = b;
         assert!(c != a && c == b)
     }
 
     #[test]
     fn scale() {
         let a = Vec3d::new(1., 2., 3.);
-        let b = Vec3d::new(3., 4., 5.);
-        let scale1 = a.clone() * 2.;
+        let scale1 = a * 2.;
         assert!(is_near(scale1.x(), 2.) && is_near(scale1.y(), 4.) && is_near(scale1.z(), 6.));
-        let scale2 = a.clone() / 2.;
+        let scale2 = a / 2.;
         assert!(is_near(scale2.x(), 0.5) && is_near(scale2.y(), 1.) && is_near(scale2.z(), 1.5));
     }
 
@@ -242,9 +293,22 @@ mod Tests {
 
     #[test]
     fn abs_normalized() {
-        let a = Vec3d::new(1., 2., 3.);
         let b = Vec3d::new(3., 4., 5.);
         assert!(is_near(b.abs(), 50.0_f64.sqrt()));
         assert!(is_near(b.normalized().abs(), 1.));
     }
+
+    #[test]
+    fn grid() {
+        let points: Vec<Vec3d> = grid_points(Vec3d::new(1., 2., 3.), Vec3d::new(2., 3., 4.), (2, 2, 2)).collect();
+        assert_eq!(points.len(), 8);
+        for x in [1., 3.] {
+            for y in [2., 5.] {
+                for z in [3., 7.] {
+                    assert!(points.contains(&Vec3d::new(x, y, z)));
+                }
+            }
+        }
+        assert_eq!(points[1], Vec3d::new(3., 2., 3.));
+    }
 }
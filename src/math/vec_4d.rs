@@ -1,6 +1,5 @@
-use std::f64::EPSILON;
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec4d([f64; 4]);
 
 impl Vec4d {
@@ -20,12 +19,20 @@ impl Vec4d {
         self.x() * self.x() + self.y() * self.y() + self.z() * self.z() + self.w() * self.w()
     }
 
-    fn abs(&self) -> f64 {
+    pub fn abs(&self) -> f64 {
         self.sqr_abs().sqrt()
     }
 
-    fn normalized(&self) -> Vec4d {
-        if self.abs() > EPSILON {
+    pub fn length(&self) -> f64 {
+        self.abs()
+    }
+
+    pub fn dot(&self, rhs: &Vec4d) -> f64 {
+        self.x() * rhs.x() + self.y() * rhs.y() + self.z() * rhs.z() + self.w() * rhs.w()
+    }
+
+    pub fn normalized(&self) -> Vec4d {
+        if self.abs() > f64::EPSILON {
             self / self.abs()
         } else {
             Vec4d::new(0.0, 0.0, 0.0, 0.0)
@@ -57,6 +64,22 @@ impl std::ops::Sub<&Vec4d> for &Vec4d {
     }
 }
 
+impl std::ops::Add<Vec4d> for &Vec4d {
+    type Output = Vec4d;
+
+    fn add(self, rhs: Vec4d) -> Self::Output {
+        *self + rhs
+    }
+}
+
+impl std::ops::Sub<Vec4d> for &Vec4d {
+    type Output = Vec4d;
+
+    fn sub(self, rhs: Vec4d) -> Self::Output {
+        *self - rhs
+    }
+}
+
 impl std::ops::Mul<f64> for &Vec4d {
     type Output = Vec4d;
 
@@ -69,7 +92,7 @@ impl std::ops::Div<f64> for &Vec4d {
     type Output = Vec4d;
 
     fn div(self, rhs: f64) -> Self::Output {
-        if rhs.abs() > EPSILON {
+        if rhs.abs() > f64::EPSILON {
             Vec4d::new(self.x() / rhs, self.y() / rhs, self.z() / rhs, self.w() / rhs)
         } else {
             panic!("Trying to div by 0")
@@ -82,7 +105,7 @@ impl PartialEq<Vec4d> for Vec4d {
         // self.x() == other.x() && self.y() == other.y()
         let diff = self - other;
 
-        diff.sqr_abs() < EPSILON
+        diff.sqr_abs() < f64::EPSILON
     }
 }
 
@@ -112,6 +135,22 @@ impl std::ops::Sub<&Vec4d> for Vec4d {
     }
 }
 
+impl std::ops::Add<Vec4d> for Vec4d {
+    type Output = Vec4d;
+
+    fn add(self, rhs: Vec4d) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl std::ops::Sub<Vec4d> for Vec4d {
+    type Output = Vec4d;
+
+    fn sub(self, rhs: Vec4d) -> Self::Output {
+        &self - &rhs
+    }
+}
+
 impl std::ops::Mul<f64> for Vec4d {
     type Output = Vec4d;
 
@@ -124,7 +163,7 @@ impl std::ops::Div<f64> for Vec4d {
     type Output = Vec4d;
 
     fn div(self, rhs: f64) -> Self::Output {
-        if rhs.abs() > EPSILON {
+        if rhs.abs() > f64::EPSILON {
             Vec4d::new(self.x() / rhs, self.y() / rhs, self.z() / rhs, self.w() / rhs)
         } else {
             panic!("Trying to div by 0")
@@ -139,7 +178,7 @@ impl Vec4d {
 }
 
 #[cfg(test)]
-mod Tests {
+mod tests {
     use crate::math::is_near;
 
     use super::Vec4d;
@@ -147,14 +186,13 @@ mod Tests {
     #[test]
     fn copy() {
         let a = Vec4d::new(1., 2., 3., 4.);
-        let b = Vec4d::new(3., 4., 5., 6.);
-        let c = a.clone();
-        assert!(is_near(c.x(), 1.) && is_near(c.y(), 2.) && is_near(c.z(), 3.) && is_near(c.w(), 4.))
+        let c = a;
+        assert!(is_near(c.x(), 1.) && is_near(c.y(), 2.) && is_near(c.z(), 3.) && is_near(c.w(), 4.));
+        assert_eq!(a, c);
     }
 
     #[test]
     fn assign() {
-        let a = Vec4d::new(1., 2., 3., 4.);
         let b = Vec4d::new(3., 4., 5., 6.);
         let c = b;
         assert!(is_near(c.x(), 3.) && is_near(c.y(), 4.) && is_near(c.z(), 5.) && is_near(c.w(), 6.))
@@ -163,7 +201,6 @@ mod Tests {
     #[test]
     fn neg() {
         let a = Vec4d::new(1., 2., 3., 4.);
-        let b = Vec4d::new(3., 4., 5., 6.);
         let neg = -a;
         assert!(is_near(neg.x(), -1.) && is_near(neg.y(), -2.) && is_near(neg.z(), -3.) && is_near(neg.w(), -4.))
     }
@@ -172,8 +209,8 @@ mod Tests {
     fn plus_minus() {
         let a = Vec4d::new(1., 2., 3., 4.);
         let b = Vec4d::new(3., 4., 5., 6.);
-        let summ = &a + &b;
-        let diff = &a - &b;
+        let summ = a + b;
+        let diff = a - b;
         assert!(is_near(summ.x(), 4.) && is_near(summ.y(), 6.) && is_near(summ.z(), 8.) && is_near(summ.w(), 10.));
         assert!(is_near(diff.x(), -2.) && is_near(diff.y(), -2.) && is_near(diff.z(), -2.) && is_near(diff.w(), -2.));
     }
@@ -182,25 +219,48 @@ mod Tests {
     fn eq() {
         let a = Vec4d::new(1., 2., 3., 4.);
         let b = Vec4d::new(3., 4., 5., 6.);
-        let c = b.clone();
+        let c = b;
         assert!(c != a && c == b)
     }
 
     #[test]
     fn scale() {
         let a = Vec4d::new(1., 2., 3., 4.);
-        let b = Vec4d::new(3., 4., 5., 6.);
-        let scale1 = a.clone() * 2.;
+        let scale1 = a * 2.;
         assert!(is_near(scale1.x(), 2.) && is_near(scale1.y(), 4.) && is_near(scale1.z(), 6.) && is_near(scale1.w(), 8.));
-        let scale2 = a.clone() / 2.;
+        let scale2 = a / 2.;
         assert!(is_near(scale2.x(), 0.5) && is_near(scale2.y(), 1.) && is_near(scale2.z(), 1.5) && is_near(scale2.w(), 2.));
     }
 
     #[test]
     fn abs_normalized() {
-        let a = Vec4d::new(1., 2., 3., 4.);
         let b = Vec4d::new(3., 4., 5., 6.);
         assert!(is_near(b.abs(), 86.0_f64.sqrt()));
         assert!(is_near(b.normalized().abs(), 1.));
     }
+
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn owned_and_borrowed_ops() {
+        let a = Vec4d::new(1., 2., 3., 4.);
+        let b = Vec4d::new(3., 4., 5., 6.);
+        let expected = Vec4d::new(4., 6., 8., 10.);
+
+        assert_eq!(a + b, expected);
+        assert_eq!(a + &b, expected);
+        assert_eq!(&a + b, expected);
+        assert_eq!(&a + &b, expected);
+
+        assert_eq!(a - b, &a - &b);
+        assert_eq!(&a - b, a - &b);
+    }
+
+    #[test]
+    fn dot_length() {
+        let a = Vec4d::new(1., 2., 3., 4.);
+        let b = Vec4d::new(3., 4., 5., 6.);
+        assert!(is_near(a.dot(&b), 50.));
+        assert!(is_near(b.length(), 86.0_f64.sqrt()));
+        assert!(is_near(a.dot(&a), a.length() * a.length()));
+    }
 }
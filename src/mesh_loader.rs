@@ -0,0 +1,304 @@
+use std::fs;
+use std::io;
+
+use macroquad::prelude::{Color, WHITE};
+
+use crate::math::vec_4d::Vec4d;
+use crate::object::{LoadedMesh, ObjectNameTag};
+use crate::triangle::Triangle;
+
+pub(crate) fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> io::Result<PlyType> {
+        Ok(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return Err(invalid(format!("PLY: unknown property type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+}
+
+enum PlyProperty {
+    Scalar(String, PlyType),
+    List(String, PlyType, PlyType),
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+// element data after the header, read value by value in either format
+struct PlyBody<'a> {
+    format: PlyFormat,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl PlyBody<'_> {
+    fn read(&mut self, ty: PlyType) -> io::Result<f64> {
+        match self.format {
+            PlyFormat::Ascii => {
+                while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+                    self.pos += 1;
+                }
+                let start = self.pos;
+                while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+                    self.pos += 1;
+                }
+                let token = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or("");
+                token
+                    .parse::<f64>()
+                    .map_err(|_| invalid(format!("PLY: expected a number, found '{}'", token)))
+            }
+            PlyFormat::BinaryLittleEndian => {
+                let bytes = self
+                    .data
+                    .get(self.pos..self.pos + ty.size())
+                    .ok_or_else(|| invalid("PLY: unexpected end of binary data".to_string()))?;
+                self.pos += ty.size();
+                Ok(match ty {
+                    PlyType::I8 => bytes[0] as i8 as f64,
+                    PlyType::U8 => bytes[0] as f64,
+                    PlyType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    PlyType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    PlyType::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    PlyType::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    PlyType::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                    PlyType::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+                })
+            }
+        }
+    }
+}
+
+// Loads a PLY mesh (ASCII or binary little endian). Polygons are split into triangle fans.
+// Optional red/green/blue vertex properties become per-vertex colors (0-255 for integer types, 0-1 for floats).
+pub fn load_ply(path: &str, nametag: ObjectNameTag) -> io::Result<LoadedMesh> {
+    let data = fs::read(path)?;
+    parse_ply(&data, nametag)
+}
+
+// same as load_ply, from the file contents
+pub fn parse_ply(data: &[u8], nametag: ObjectNameTag) -> io::Result<LoadedMesh> {
+    let header_end = data
+        .windows(b"end_header".len())
+        .position(|w| w == b"end_header")
+        .ok_or_else(|| invalid("PLY: missing end_header".to_string()))?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| header_end + i + 1);
+    let header = std::str::from_utf8(&data[..header_end]).map_err(|_| invalid("PLY: header is not text".to_string()))?;
+
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err(invalid("PLY: file must start with 'ply'".to_string()));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = vec![];
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(PlyFormat::BinaryLittleEndian),
+            ["format", other, ..] => return Err(invalid(format!("PLY: unsupported format '{}'", other))),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("PLY: bad element count in '{}'", line)))?,
+                properties: vec![],
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid(format!("PLY: property before any element: '{}'", line)))?
+                .properties
+                .push(PlyProperty::List(name.to_string(), PlyType::parse(count_ty)?, PlyType::parse(item_ty)?)),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid(format!("PLY: property before any element: '{}'", line)))?
+                .properties
+                .push(PlyProperty::Scalar(name.to_string(), PlyType::parse(ty)?)),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(invalid(format!("PLY: unexpected header line '{}'", line))),
+        }
+    }
+
+    let mut body = PlyBody {
+        format: format.ok_or_else(|| invalid("PLY: missing format line".to_string()))?,
+        data: &data[body_start..],
+        pos: 0,
+    };
+
+    let mut positions: Vec<Vec4d> = vec![];
+    let mut colors: Vec<Option<Color>> = vec![];
+    let mut faces: Vec<Vec<usize>> = vec![];
+
+    for element in elements.iter() {
+        for _ in 0..element.count {
+            let (mut xyz, mut rgb) = ([0.0; 3], [None; 3]);
+            let mut indices = vec![];
+
+            for property in element.properties.iter() {
+                match property {
+                    PlyProperty::Scalar(name, ty) => {
+                        let value = body.read(*ty)?;
+                        let color_scale = if matches!(ty, PlyType::F32 | PlyType::F64) { 1.0 } else { 255.0 };
+                        match name.as_str() {
+                            "x" => xyz[0] = value,
+                            "y" => xyz[1] = value,
+                            "z" => xyz[2] = value,
+                            "red" => rgb[0] = Some((value / color_scale) as f32),
+                            "green" => rgb[1] = Some((value / color_scale) as f32),
+                            "blue" => rgb[2] = Some((value / color_scale) as f32),
+                            _ => {}
+                        }
+                    }
+                    PlyProperty::List(name, count_ty, item_ty) => {
+                        let count = body.read(*count_ty)? as usize;
+                        for _ in 0..count {
+                            let value = body.read(*item_ty)?;
+                            if name == "vertex_indices" || name == "vertex_index" {
+                                indices.push(value as usize);
+                            }
+                        }
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    positions.push(Vec4d::new(xyz[0], xyz[1], xyz[2], 1.0));
+                    colors.push(match rgb {
+                        [Some(r), Some(g), Some(b)] => Some(Color::new(r, g, b, 1.0)),
+                        _ => None,
+                    });
+                }
+                "face" => faces.push(indices),
+                _ => {}
+            }
+        }
+    }
+
+    let mut triangles = vec![];
+    for face in faces.iter() {
+        if let Some(&bad) = face.iter().find(|&&i| i >= positions.len()) {
+            return Err(invalid(format!("PLY: face references missing vertex {}", bad)));
+        }
+
+        for k in 1..face.len().saturating_sub(1) {
+            let ids = [face[0], face[k], face[k + 1]];
+            let [p1, p2, p3] = ids.map(|i| positions[i]);
+            triangles.push(match ids.map(|i| colors[i]) {
+                [Some(a), Some(b), Some(c)] => Triangle::new_shaded([a, b, c], p1, p2, p3),
+                _ => Triangle::new(WHITE, p1, p2, p3),
+            });
+        }
+    }
+
+    Ok(LoadedMesh::new(nametag, triangles, WHITE))
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::Color;
+
+    use crate::object::{Mesh, ObjectNameTag};
+
+    use super::{load_ply, parse_ply};
+
+    const QUAD: &str = "ply
+format ascii 1.0
+comment colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn ascii_ply_with_vertex_colors() {
+        let path = std::env::temp_dir().join("rust_3d_course_quad.ply");
+        std::fs::write(&path, QUAD).unwrap();
+        let mesh = load_ply(path.to_str().unwrap(), ObjectNameTag::new("Quad")).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mesh.triangles().len(), 2);
+        let colors = mesh.triangles()[0].vertex_colors().unwrap();
+        assert_eq!(colors[0], Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(colors[1], Color::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(colors[2], Color::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(mesh.triangles()[1].vertex_colors().unwrap()[2], Color::new(1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn binary_ply() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        for v in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for c in v {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        data.push(3);
+        for i in [0i32, 1, 2] {
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let mesh = parse_ply(&data, ObjectNameTag::new("Tri")).unwrap();
+        assert_eq!(mesh.triangles().len(), 1);
+        assert!(mesh.triangles()[0].vertex_colors().is_none());
+        assert_eq!(mesh.triangles()[0].points()[1].x(), 1.0);
+
+        assert!(parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nend_header\nfoo\n", ObjectNameTag::new("Bad")).is_err());
+    }
+}
@@ -1,15 +1,21 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f64::consts::PI;
 use std::rc::{self, Rc};
 
-use macroquad::prelude::Color;
+use macroquad::prelude::{Color, WHITE};
+use rand::{Rng, RngCore};
 
+use crate::adjacency::{edge, vertex_key, Adjacency, Edge, VertexKey};
+use crate::material::Material;
 use crate::math::matrix4x4::Matrix4x4;
+use crate::math::plane::Plane;
+use crate::math::quaternion::Quaternion;
 use crate::math::vec_3d::Vec3d;
 use crate::math::vec_4d::Vec4d;
-use crate::triangle::Triangle;
+use crate::triangle::{Triangle, TriangleTransform};
 
-#[derive(Debug, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct ObjectNameTag(String);
 
 impl ObjectNameTag {
@@ -26,18 +32,39 @@ impl ObjectNameTag {
     }
 }
 
-impl PartialEq for ObjectNameTag {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-
 impl PartialOrd for ObjectNameTag {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         self.0.partial_cmp(&other.0)
     }
 }
 
+// Position, orientation and per-axis scale of an object.
+#[derive(Debug, Clone)]
+pub struct Pose {
+    pub position: Vec3d,
+    pub rotation: Quaternion,
+    pub scale: Vec3d,
+}
+
+impl Pose {
+    pub fn new(position: Vec3d, rotation: Quaternion, scale: Vec3d) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    // position and scale are lerped, rotation is slerped
+    pub fn lerp(a: &Pose, b: &Pose, t: f64) -> Pose {
+        Pose {
+            position: a.position + ((b.position - a.position) * t),
+            rotation: a.rotation.slerp(&b.rotation, t),
+            scale: a.scale + ((b.scale - a.scale) * t),
+        }
+    }
+}
+
 pub trait Object {
     fn nametag(&self) -> &ObjectNameTag;
     fn nametag_mut(&mut self) -> &mut ObjectNameTag;
@@ -66,9 +93,9 @@ pub trait Object {
 
     fn transform(&mut self, matrix: &Matrix4x4) {
         *self.transform_matrix_mut() = self.transform_matrix().clone() * matrix.clone();
-        let position = self.position().clone();
+        let position = *self.position();
 
-        for (_, object) in self.attached_objects_mut() {
+        for object in self.attached_objects_mut().values() {
             if let Some(o) = object.upgrade() {
                 o.borrow_mut().transform_relative_point(&position, matrix);
             }
@@ -77,14 +104,15 @@ pub trait Object {
 
     fn transform_relative_point(&mut self, point: &Vec3d, transform: &Matrix4x4) {
         // translate object in new coordinate system (connected with point)
-        *self.transform_matrix_mut() = Matrix4x4::translation(&(self.position().clone() - point.clone())) * transform.clone();
+        *self.transform_matrix_mut() =
+            Matrix4x4::translation(&(*self.position() - *point)) * self.transform_matrix().clone();
         // transform object in the new coordinate system
         *self.transform_matrix_mut() = transform.clone() * self.transform_matrix().clone();
         // translate object back in self connected coordinate system
-        *self.position_mut() = self.transform_matrix().w() + point.clone();
+        *self.position_mut() = self.transform_matrix().w() + *point;
         *self.transform_matrix_mut() = Matrix4x4::translation(&-self.transform_matrix().w()) * self.transform_matrix().clone();
 
-        for (_, object) in self.attached_objects_mut() {
+        for object in self.attached_objects_mut().values() {
             if let Some(o) = object.upgrade() {
                 o.borrow_mut().transform_relative_point(point, transform);
             }
@@ -93,9 +121,9 @@ pub trait Object {
 
     fn translate(&mut self, dv: &Vec3d) {
         let pos = self.position();
-        *self.position_mut() = pos.clone() + dv.clone();
+        *self.position_mut() = *pos + *dv;
 
-        for (_, object) in self.attached_objects_mut() {
+        for object in self.attached_objects_mut().values() {
             if let Some(o) = object.upgrade() {
                 o.borrow_mut().translate(dv)
             }
@@ -107,7 +135,7 @@ pub trait Object {
     }
 
     fn rotate(&mut self, r: &Vec3d) {
-        *self.angle_mut() = self.angle().clone() + r.clone();
+        *self.angle_mut() = *self.angle() + *r;
 
         self.transform(&Matrix4x4::rotation(r));
     }
@@ -116,8 +144,18 @@ pub trait Object {
         self.transform(&Matrix4x4::rotation_around_vec(v, rv));
     }
 
+    // local rotation like rotate, without the euler angle bookkeeping (angle() is left as it is)
+    fn rotate_quat(&mut self, q: &Quaternion) {
+        self.transform(&q.normalized().to_matrix());
+    }
+
+    // re-orthonormalizes the rotation after many incremental rotations made it drift (drops any scale)
+    fn orthonormalize(&mut self) {
+        *self.transform_matrix_mut() = self.transform_matrix().orthonormalized();
+    }
+
     fn rotate_relative_point(&mut self, s: &Vec3d, r: &Vec3d) {
-        *self.angle_mut() = self.angle().clone() + r.clone();
+        *self.angle_mut() = *self.angle() + *r;
 
         self.transform_relative_point(s, &Matrix4x4::rotation(r));
     }
@@ -157,12 +195,12 @@ pub trait Object {
     }
 
     fn translate_to_point(&mut self, point: &Vec3d) {
-        self.translate(&(point.clone() - self.position().clone()));
+        self.translate(&(*point - *self.position()));
     }
 
     fn attract_to_point(&mut self, point: &Vec3d, value: f64) {
         let v = (point - self.position()).normalized();
-        self.translate(&(&v * value));
+        self.translate(&(v * value));
     }
 
     fn rotate_to_angle(&mut self, angle: &Vec3d) {
@@ -209,6 +247,84 @@ pub trait Object {
         self.attached_objects_mut().remove(tag);
     }
 
+    // all live attached objects, children before grandchildren; dropped ones are skipped
+    fn descendants(&self) -> Vec<Rc<RefCell<dyn Object>>> {
+        let mut res = vec![];
+        let mut queue: VecDeque<Rc<RefCell<dyn Object>>> =
+            self.attached_objects().values().filter_map(|o| o.upgrade()).collect();
+
+        while let Some(obj) = queue.pop_front() {
+            queue.extend(obj.borrow().attached_objects().values().filter_map(|o| o.upgrade()));
+            res.push(obj);
+        }
+
+        res
+    }
+
+    // meshes return themselves, so hierarchies of plain objects can be searched for geometry
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        None
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        None
+    }
+
+    // shows or hides this object and everything attached below it, objects without geometry are skipped
+    fn set_subtree_visible(&mut self, visible: bool) {
+        if let Some(mesh) = self.as_mesh_mut() {
+            *mesh.visible_mut() = visible;
+        }
+        for obj in self.descendants() {
+            if let Some(mesh) = obj.borrow_mut().as_mesh_mut() {
+                *mesh.visible_mut() = visible;
+            }
+        }
+    }
+
+    // decomposes the transform matrix into rotation and scale (no shear expected)
+    fn pose(&self) -> Pose {
+        let m = self.transform_matrix();
+        let scale = Vec3d::new(m.x().abs(), m.y().abs(), m.z().abs());
+        let mut axes = [m.x().normalized(), m.y().normalized(), m.z().normalized()];
+        // a zero scale leaves an axis with no direction: one is rebuilt from the other two,
+        // with two or more gone there is no rotation to read back
+        let collapsed: Vec<usize> = (0..3).filter(|&i| axes[i].sqr_abs() < 0.5).collect();
+        let rotation = match collapsed[..] {
+            [] | [_] => {
+                if let [i] = collapsed[..] {
+                    axes[i] = axes[(i + 1) % 3].cross(&axes[(i + 2) % 3]);
+                }
+                let [x, y, z] = axes;
+                Quaternion::from_matrix(&Matrix4x4::from_array([
+                    x.x(), x.y(), x.z(), 0.0, y.x(), y.y(), y.z(), 0.0, z.x(), z.y(), z.z(), 0.0, 0.0, 0.0, 0.0, 1.0,
+                ]))
+            }
+            _ => Quaternion::identity(),
+        };
+        Pose::new(*self.position(), rotation, scale)
+    }
+
+    fn set_pose(&mut self, pose: &Pose) {
+        let m = pose.rotation.to_matrix() * Matrix4x4::scale(&pose.scale);
+        // attached objects turn and scale along about our position, like in transform;
+        // a zero scale can't be undone, then they only follow the position
+        if let Some(inv) = self.transform_matrix().inverse() {
+            let change = m.clone() * inv;
+            let position = *self.position();
+            for object in self.attached_objects_mut().values() {
+                if let Some(o) = object.upgrade() {
+                    o.borrow_mut().transform_relative_point(&position, &change);
+                }
+            }
+        }
+        *self.transform_matrix_mut() = m;
+        self.translate_to_point(&pose.position);
+    }
+
+    fn lerp_pose(&mut self, a: &Pose, b: &Pose, t: f64) {
+        self.set_pose(&Pose::lerp(a, b, t));
+    }
+
     fn model(&self) -> Matrix4x4 {
         Matrix4x4::translation(self.position()) * self.transform_matrix().clone()
     }
@@ -225,8 +341,490 @@ pub trait Mesh: Object {
     fn triangles_mut(&mut self) -> &mut Vec<Triangle>;
     fn visible(&self) -> bool;
     fn visible_mut(&mut self) -> &mut bool;
+    fn materials(&self) -> &Vec<Material>;
+    fn materials_mut(&mut self) -> &mut Vec<Material>;
+    // Order project emits the triangle groups in (e.g. inner parts before a transparent case), triangles
+    // of groups not listed come last. Empty keeps the triangle order. The camera's depth sorts ignore it.
+    fn group_order(&self) -> &Vec<u32>;
+    fn group_order_mut(&mut self) -> &mut Vec<u32>;
+    fn geometry_cache(&self) -> &GeometryCache;
+
+    // edge adjacency of the triangles, built on first use and kept until triangles_mut is called
+    fn adjacency(&self) -> Rc<Adjacency> {
+        self.geometry_cache()
+            .adjacency
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(Adjacency::build(self.triangles())))
+            .clone()
+    }
+
+    fn material(&self, tri: &Triangle) -> Option<&Material> {
+        tri.material_id().and_then(|id| self.materials().get(id as usize))
+    }
+
+    // base color of the triangle: its material's color if it has one, otherwise its own color
+    fn material_color(&self, tri: &Triangle) -> Color {
+        self.material(tri).map_or(tri.color(), |material| material.color())
+    }
+
+    // triangles transformed into world space
+    fn world_triangles(&self) -> Vec<Triangle> {
+        let m = TriangleTransform::new(&self.model());
+        self.triangles().iter().map(|t| t.transform_by(&m)).collect()
+    }
+
+    // Local space sphere around all vertices: centered on their bounding box, reaching the farthest one.
+    // Kept until triangles_mut is called. An empty mesh gives a zero radius at the origin.
+    fn local_bounding_sphere(&self) -> (Vec3d, f64) {
+        let cache = &self.geometry_cache().bounding_sphere;
+        if let Some(sphere) = cache.get() {
+            return sphere;
+        }
+
+        let sphere = match self.local_aabb() {
+            Some((min, max)) => {
+                let center = (min + max) * 0.5;
+                let points = self.triangles().iter().flat_map(|t| t.points().iter());
+                (center, points.map(|p| (Vec3d::from_vec4d(p) - center).abs()).fold(0.0, f64::max))
+            }
+            None => (Vec3d::new(0.0, 0.0, 0.0), 0.0),
+        };
+        cache.set(Some(sphere));
+        sphere
+    }
+
+    // local_bounding_sphere moved into world space, the radius grows with the largest axis scale
+    fn bounding_sphere(&self) -> (Vec3d, f64) {
+        let (center, radius) = self.local_bounding_sphere();
+        let m = self.transform_matrix();
+        let scale = m.x().abs().max(m.y().abs()).max(m.z().abs());
+        (Vec3d::from_vec4d(&(&self.model() * &center.make_point_4d())), radius * scale)
+    }
+
+    // Distance from a world space point to the nearest point of the mesh surface,
+    // negative when the point is inside (the mesh is expected to be closed with outward normals).
+    fn signed_distance(&self, p: &Vec3d) -> f64 {
+        let triangles = self.world_triangles();
+
+        let distance = triangles
+            .iter()
+            .map(|t| (&t.closest_point(p) - p).abs())
+            .fold(f64::INFINITY, f64::min);
+
+        // generalized winding number: ~1 inside, ~0 outside
+        let winding = triangles.iter().map(|t| t.solid_angle(p)).sum::<f64>() / (4.0 * std::f64::consts::PI);
+
+        if winding.abs() > 0.5 {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    // Sets per-vertex normals averaged over the faces sharing each vertex.
+    // Faces whose normals differ by more than crease_angle (radians) are not averaged
+    // together, so hard edges stay sharp.
+    fn compute_smooth_normals(&mut self, crease_angle: f64) {
+        let min_dot = crease_angle.cos();
+
+        let mut faces_at_vertex: HashMap<VertexKey, Vec<usize>> = HashMap::new();
+        for (i, tri) in self.triangles().iter().enumerate() {
+            for p in tri.points() {
+                faces_at_vertex.entry(vertex_key(p)).or_default().push(i);
+            }
+        }
+
+        let normals: Vec<[Vec3d; 3]> = self
+            .triangles()
+            .iter()
+            .map(|tri| {
+                let face_normal = tri.normal();
+                (*tri.points()).map(|p| {
+                    let mut sum = Vec3d::new(0.0, 0.0, 0.0);
+                    for &j in &faces_at_vertex[&vertex_key(&p)] {
+                        let other = self.triangles()[j].normal();
+                        if face_normal.dot(&other) >= min_dot {
+                            sum = sum + other;
+                        }
+                    }
+                    sum.normalized()
+                })
+            })
+            .collect();
+
+        for (tri, vertex_normals) in self.triangles_mut().iter_mut().zip(normals) {
+            *tri.vertex_normals_mut() = Some(vertex_normals);
+        }
+    }
+
+    // Every edge of the triangles once, in local space and in order of first use. Endpoints are
+    // matched by position (within ~1e-6, like adjacency), in either direction.
+    fn unique_edges(&self) -> Vec<(Vec3d, Vec3d)> {
+        let mut seen = HashSet::new();
+        let mut res = vec![];
+
+        for tri in self.triangles() {
+            let p = tri.points();
+            for (a, b) in [(&p[0], &p[1]), (&p[1], &p[2]), (&p[2], &p[0])] {
+                if seen.insert(edge(a, b)) {
+                    res.push((Vec3d::from_vec4d(a), Vec3d::from_vec4d(b)));
+                }
+            }
+        }
+
+        res
+    }
+
+    // (min, max) corners of the triangles in local space, None for an empty mesh
+    fn local_aabb(&self) -> Option<(Vec3d, Vec3d)> {
+        let mut points = self.triangles().iter().flat_map(|t| t.points().iter());
+        let first = Vec3d::from_vec4d(points.next()?);
+        let (mut min, mut max) = (first, first);
+        for p in points {
+            min = Vec3d::new(min.x().min(p.x()), min.y().min(p.y()), min.z().min(p.z()));
+            max = Vec3d::new(max.x().max(p.x()), max.y().max(p.y()), max.z().max(p.z()));
+        }
+        Some((min, max))
+    }
+
+    // Moves and uniformly scales the triangles so their bounding box is centered at the origin
+    // and its longest side is 1. Returns the applied matrix (its inverse undoes it).
+    fn normalize_to_unit(&mut self) -> Matrix4x4 {
+        let Some((min, max)) = self.local_aabb() else {
+            return Matrix4x4::identity();
+        };
+
+        let size = max - min;
+        let extent = size.x().max(size.y()).max(size.z());
+        let scale = if extent > f64::EPSILON { 1.0 / extent } else { 1.0 };
+        let m = Matrix4x4::scale(&Vec3d::new(scale, scale, scale)) * Matrix4x4::translation(&-((min + max) / 2.0));
+
+        let triangles: Vec<Triangle> = self.triangles().iter().map(|t| t * &m).collect();
+        *self.triangles_mut() = triangles;
+
+        m
+    }
+
+    // n world space points spread uniformly over the surface: triangles are picked with probability
+    // proportional to their area, then a uniform point inside the picked triangle. Empty for a mesh without area.
+    fn sample_surface(&self, n: usize, rng: &mut dyn RngCore) -> Vec<Vec3d> {
+        let triangles = self.world_triangles();
+        let cumulative: Vec<f64> = triangles
+            .iter()
+            .scan(0.0, |total, t| {
+                *total += t.area();
+                Some(*total)
+            })
+            .collect();
+        let total = cumulative.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return vec![];
+        }
+
+        (0..n)
+            .map(|_| {
+                let pick = rng.gen::<f64>() * total;
+                let i = cumulative.partition_point(|&c| c <= pick).min(triangles.len() - 1);
+                let [a, b, c] = (*triangles[i].points()).map(|p| Vec3d::from_vec4d(&p));
+
+                // folding the unit square onto the triangle keeps the density uniform
+                let (mut u, mut v) = (rng.gen::<f64>(), rng.gen::<f64>());
+                if u + v > 1.0 {
+                    (u, v) = (1.0 - u, 1.0 - v);
+                }
+                a + (((b - a) * u) + ((c - a) * v))
+            })
+            .collect()
+    }
+
+    // Segments where the (world space) plane cuts the world space triangles, one per triangle
+    // crossing it. Triangles only touching the plane with a vertex, or lying in it, give none.
+    fn cross_section(&self, plane: &Plane) -> Vec<(Vec3d, Vec3d)> {
+        let mut res = vec![];
+
+        for tri in self.world_triangles() {
+            let points = (*tri.points()).map(|p| Vec3d::from_vec4d(&p));
+            let d = points.map(|p| plane.distance(&p));
+
+            let mut cut: Vec<Vec3d> = vec![];
+            for i in 0..3 {
+                let j = (i + 1) % 3;
+                if d[i] == 0.0 {
+                    cut.push(points[i]);
+                } else if (d[i] < 0.0) != (d[j] < 0.0) && d[j] != 0.0 {
+                    let k = d[i] / (d[i] - d[j]);
+                    cut.push(points[i] + ((points[j] - points[i]) * k));
+                }
+            }
+
+            if let [a, b] = cut.as_slice() {
+                res.push((*a, *b));
+            }
+        }
+
+        res
+    }
+
+    // Snaps vertices closer than tolerance to each other onto one shared position, so triangle soup
+    // (e.g. from STL files) gets connected edges. Triangles collapsing to a line or a point are
+    // removed. Returns the number of distinct vertex positions the remaining triangles use.
+    fn weld_vertices(&mut self, tolerance: f64) -> usize {
+        let cell_size = tolerance.max(f64::EPSILON);
+        let cell = |p: &Vec4d| {
+            (
+                (p.x() / cell_size).floor() as i64,
+                (p.y() / cell_size).floor() as i64,
+                (p.z() / cell_size).floor() as i64,
+            )
+        };
+
+        // welded positions bucketed by grid cell, a match can only be in a neighbouring cell
+        let mut positions: Vec<Vec4d> = vec![];
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut weld = |p: &Vec4d| -> usize {
+            let (cx, cy, cz) = cell(p);
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let Some(ids) = grid.get(&(cx + dx, cy + dy, cz + dz)) else {
+                            continue;
+                        };
+                        if let Some(&id) = ids.iter().find(|&&id| (positions[id] - p).abs() <= tolerance) {
+                            return id;
+                        }
+                    }
+                }
+            }
+            positions.push(*p);
+            grid.entry((cx, cy, cz)).or_default().push(positions.len() - 1);
+            positions.len() - 1
+        };
+
+        let welded: Vec<[usize; 3]> = self.triangles().iter().map(|t| (*t.points()).map(|p| weld(&p))).collect();
+        let kept = |[a, b, c]: &&[usize; 3]| a != b && b != c && c != a;
+        let triangles: Vec<Triangle> = self
+            .triangles()
+            .iter()
+            .zip(welded.iter())
+            .filter(|(_, ids)| kept(ids))
+            .map(|(t, [a, b, c])| t.with_points(positions[*a], positions[*b], positions[*c]))
+            .collect();
+        *self.triangles_mut() = triangles;
+
+        // positions only the removed triangles used don't count
+        welded.iter().filter(kept).flatten().collect::<HashSet<_>>().len()
+    }
+
+    // Splits every triangle into four at its edge midpoints, levels times (4^levels times the triangles).
+    // Per-vertex attributes are interpolated and face normals recomputed. Neighbours get the same midpoint
+    // for their shared edge, so a closed mesh stays closed.
+    fn subdivide(&mut self, levels: u32) {
+        for _ in 0..levels {
+            let mut midpoints: HashMap<Edge, Vec4d> = HashMap::new();
+            let mut midpoint = |a: &Vec4d, b: &Vec4d| *midpoints.entry(edge(a, b)).or_insert_with(|| (*a + *b) * 0.5);
+
+            let mut triangles = Vec::with_capacity(4 * self.triangles().len());
+            for tri in self.triangles() {
+                let p = tri.points();
+                let (m01, m12, m20) = (midpoint(&p[0], &p[1]), midpoint(&p[1], &p[2]), midpoint(&p[2], &p[0]));
+                let (h01, h12, h20) = ((0, 1, 0.5), (1, 2, 0.5), (2, 0, 0.5));
+
+                triangles.push(tri.lerp_vertices([(0, 0, 0.0), h01, h20]).with_points(p[0], m01, m20));
+                triangles.push(tri.lerp_vertices([h01, (1, 1, 0.0), h12]).with_points(m01, p[1], m12));
+                triangles.push(tri.lerp_vertices([h20, h12, (2, 2, 0.0)]).with_points(m20, m12, p[2]));
+                triangles.push(tri.lerp_vertices([h01, h12, h20]).with_points(m01, m12, m20));
+            }
+            *self.triangles_mut() = triangles;
+        }
+    }
+
+    // Moves every vertex along its direction from the local origin to distance radius, with face normals
+    // recomputed and vertex normals pointing straight out. After subdivide on an Icosahedron this gives
+    // a geodesic sphere.
+    fn project_to_sphere(&mut self, radius: f64) {
+        let on_sphere = |p: &Vec4d| (Vec3d::from_vec4d(p).normalized() * radius).make_point_4d();
+        let triangles: Vec<Triangle> = self
+            .triangles()
+            .iter()
+            .map(|t| {
+                let p = t.points();
+                let mut res = t.with_points(on_sphere(&p[0]), on_sphere(&p[1]), on_sphere(&p[2]));
+                *res.vertex_normals_mut() = Some((*p).map(|p| Vec3d::from_vec4d(&p).normalized()));
+                res
+            })
+            .collect();
+        *self.triangles_mut() = triangles;
+    }
+
+    // Applies model() to the triangles and resets the transform, position and angles,
+    // so local space becomes the current pose. World space triangles stay where they are.
+    fn bake_transform(&mut self) {
+        let m = TriangleTransform::new(&self.model());
+        let triangles: Vec<Triangle> = self.triangles().iter().map(|t| t.transform_by(&m)).collect();
+        *self.triangles_mut() = triangles;
+
+        *self.transform_matrix_mut() = Matrix4x4::identity();
+        *self.position_mut() = Vec3d::new(0.0, 0.0, 0.0);
+        *self.angle_mut() = Vec3d::new(0.0, 0.0, 0.0);
+        *self.angle_left_up_look_at_mut() = Vec3d::new(0.0, 0.0, 0.0);
+    }
+
+    // Flood fills the winding of each connected part from one triangle to its neighbours, flipping
+    // those that run a shared edge in the same direction, then flips whole parts with a negative
+    // signed volume so closed meshes end up with outward normals. Returns the number of flipped triangles.
+    fn make_winding_consistent(&mut self) -> usize {
+        let adjacency = self.adjacency();
+        let triangles = self.triangles();
+        let directed: Vec<[(VertexKey, VertexKey); 3]> = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = (*t.points()).map(|p| vertex_key(&p));
+                [(a, b), (b, c), (c, a)]
+            })
+            .collect();
+        let volume = |t: &Triangle| {
+            let [a, b, c] = (*t.points()).map(|p| Vec3d::from_vec4d(&p));
+            a.dot(&b.cross(&c))
+        };
+
+        let mut flip = vec![false; triangles.len()];
+        let mut visited = vec![false; triangles.len()];
+        for start in 0..triangles.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+
+            let mut part = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(i) = queue.pop_front() {
+                for j in adjacency.neighbors(i) {
+                    if visited[j] {
+                        continue;
+                    }
+                    let same_direction = directed[i].iter().any(|e| directed[j].contains(e));
+                    flip[j] = flip[i] != same_direction;
+                    visited[j] = true;
+                    part.push(j);
+                    queue.push_back(j);
+                }
+            }
+
+            let signed_volume: f64 = part
+                .iter()
+                .map(|&i| if flip[i] { -volume(&triangles[i]) } else { volume(&triangles[i]) })
+                .sum();
+            if signed_volume < 0.0 {
+                for &i in part.iter() {
+                    flip[i] = !flip[i];
+                }
+            }
+        }
+
+        let flipped = flip.iter().filter(|&&f| f).count();
+        if flipped > 0 {
+            let triangles: Vec<Triangle> = triangles
+                .iter()
+                .zip(flip.iter())
+                .map(|(t, &f)| if f { t.flipped() } else { t.clone() })
+                .collect();
+            *self.triangles_mut() = triangles;
+        }
+
+        flipped
+    }
+
+    // Moves the triangles so their area weighted centroid is the local origin and shifts the position
+    // to compensate, so the mesh spins about its middle without moving in the world.
+    // Attached objects stay where they are. Returns the old local centroid.
+    fn recenter_pivot(&mut self) -> Vec3d {
+        let (mut sum, mut total_area) = (Vec3d::new(0.0, 0.0, 0.0), 0.0);
+        for tri in self.triangles() {
+            let area = tri.area();
+            sum = sum + tri.position() * area;
+            total_area += area;
+        }
+        if total_area <= f64::EPSILON {
+            return Vec3d::new(0.0, 0.0, 0.0);
+        }
+        let centroid = sum / total_area;
+
+        let m = Matrix4x4::translation(&-&centroid);
+        let triangles: Vec<Triangle> = self.triangles().iter().map(|t| t * &m).collect();
+        *self.triangles_mut() = triangles;
+
+        *self.position_mut() = self.position() + &(self.transform_matrix() * &centroid);
+
+        centroid
+    }
+
+    // Groups triangles sharing vertices into separate meshes with this mesh's pose and materials.
+    fn split_connected(&self) -> Vec<LoadedMesh> {
+        let triangles = self.triangles();
+
+        // union-find over triangle indices
+        let mut parent: Vec<usize> = (0..triangles.len()).collect();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let mut first_at_vertex: HashMap<VertexKey, usize> = HashMap::new();
+        for (i, tri) in triangles.iter().enumerate() {
+            for p in tri.points() {
+                let j = *first_at_vertex.entry(vertex_key(p)).or_insert(i);
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+
+        let mut component_of_root: HashMap<usize, usize> = HashMap::new();
+        let mut components: Vec<Vec<Triangle>> = vec![];
+        for (i, tri) in triangles.iter().enumerate() {
+            let root = find(&mut parent, i);
+            let c = *component_of_root.entry(root).or_insert_with(|| {
+                components.push(vec![]);
+                components.len() - 1
+            });
+            components[c].push(tri.clone());
+        }
+
+        components
+            .into_iter()
+            .enumerate()
+            .map(|(i, triangles)| {
+                let nametag = ObjectNameTag::new(&format!("{}_{}", self.nametag().name(), i));
+                let mut mesh = LoadedMesh::new(nametag, triangles, self.color());
+                *mesh.transform_matrix_mut() = self.transform_matrix().clone();
+                *mesh.position_mut() = *self.position();
+                *mesh.angle_mut() = *self.angle();
+                *mesh.angle_left_up_look_at_mut() = *self.angle_left_up_look_at();
+                *mesh.materials_mut() = self.materials().clone();
+                mesh
+            })
+            .collect()
+    }
 }
 
+// Data derived from a mesh's triangles, built on first use and dropped whenever the triangles are
+// handed out mutably.
+#[derive(Default)]
+pub struct GeometryCache {
+    adjacency: RefCell<Option<Rc<Adjacency>>>,
+    bounding_sphere: Cell<Option<(Vec3d, f64)>>,
+}
+
+impl GeometryCache {
+    pub fn invalidate(&mut self) {
+        *self.adjacency.get_mut() = None;
+        *self.bounding_sphere.get_mut() = None;
+    }
+}
+
+#[derive(Clone)]
 pub struct ObjectStruct {
     pub nametag: ObjectNameTag,
     pub transform: Matrix4x4,
@@ -252,8 +850,11 @@ impl ObjectStruct {
 pub struct Cube {
     obj: ObjectStruct,
     color: Color,
-    pub triangles: Vec<Triangle>,
+    triangles: Vec<Triangle>,
     visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
 }
 
 impl Object for Cube {
@@ -293,6 +894,12 @@ impl Object for Cube {
     fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
         &mut self.obj.attached_objects
     }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
 }
 
 impl Mesh for Cube {
@@ -306,6 +913,7 @@ impl Mesh for Cube {
         &self.triangles
     }
     fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
         &mut self.triangles
     }
     fn visible(&self) -> bool {
@@ -314,13 +922,29 @@ impl Mesh for Cube {
     fn visible_mut(&mut self) -> &mut bool {
         &mut self.visible
     }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
 }
 
 impl std::ops::MulAssign<&Matrix4x4> for Cube {
     fn mul_assign(&mut self, rhs: &Matrix4x4) {
+        let m = TriangleTransform::new(rhs);
         let mut new_tries = Vec::with_capacity(self.triangles().len());
         for tri in self.triangles() {
-            new_tries.push(tri * rhs);
+            new_tries.push(tri.transform_by(&m));
         }
         *self.triangles_mut() = new_tries;
     }
@@ -342,6 +966,9 @@ impl Cube {
             color,
             triangles: vec![],
             visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
         };
 
         // 1
@@ -434,4 +1061,1831 @@ impl Cube {
 
         cube
     }
+
+    // Cube with one color per face, in +X, -X, +Y, -Y, +Z, -Z order (same as CubeMap).
+    // The face colors are its materials, so they don't follow the mesh color.
+    pub fn new_colored(nametag: ObjectNameTag, size: f64, colors: [Color; 6]) -> Cube {
+        let mut cube = Cube::new(nametag, size, WHITE);
+        cube.materials = colors.map(Material::new).to_vec();
+
+        // faces of the triangle pairs above: -Z, +X, +Z, -X, +Y, -Y
+        const FACES: [usize; 6] = [5, 0, 4, 1, 2, 3];
+        for (i, tri) in cube.triangles.iter_mut().enumerate() {
+            *tri.color_mut() = colors[FACES[i / 2]];
+            *tri.material_id_mut() = Some(FACES[i / 2] as u32);
+        }
+
+        cube
+    }
+}
+
+pub struct Sphere {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Sphere {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Sphere {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Sphere {
+    // UV sphere centered at the origin: rings latitude circles from pole to pole (poles included, at
+    // least 3) and sectors meridians (at least 3). Bands touching a pole give one triangle per sector,
+    // the others two, so there are 2 * sectors * (rings - 2) triangles, all with outward normals.
+    pub fn new(nametag: ObjectNameTag, radius: f64, rings: usize, sectors: usize, color: Color) -> Sphere {
+        let (rings, sectors) = (rings.max(3), sectors.max(3));
+        let point = |ring: usize, sector: usize| {
+            let theta = PI * ring as f64 / (rings - 1) as f64;
+            let phi = 2.0 * PI * sector as f64 / sectors as f64;
+            Vec4d::new(
+                radius * theta.sin() * phi.cos(),
+                radius * theta.cos(),
+                radius * theta.sin() * phi.sin(),
+                1.0,
+            )
+        };
+
+        let mut triangles = Vec::with_capacity(2 * sectors * (rings - 2));
+        for ring in 0..rings - 1 {
+            for sector in 0..sectors {
+                let a = point(ring, sector);
+                let b = point(ring, sector + 1);
+                let c = point(ring + 1, sector + 1);
+                let d = point(ring + 1, sector);
+
+                // a and b are the same point at the north pole, c and d at the south pole
+                if ring != 0 {
+                    triangles.push(Triangle::new(color, a, b, c));
+                }
+                if ring != rings - 2 {
+                    triangles.push(Triangle::new(color, a, c, d));
+                }
+            }
+        }
+
+        Sphere {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles,
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+pub struct Cylinder {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Cylinder {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Cylinder {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Cylinder {
+    // Cylinder around the y axis, centered at the origin, with segments (at least 3) side quads.
+    // Side vertex normals point radially outward; capped closes both ends with fans facing +Y and -Y.
+    pub fn new(
+        nametag: ObjectNameTag,
+        radius: f64,
+        height: f64,
+        segments: usize,
+        capped: bool,
+        color: Color,
+    ) -> Cylinder {
+        let segments = segments.max(3);
+        let half = 0.5 * height;
+        let radial = |sector: usize| {
+            let phi = 2.0 * PI * (sector % segments) as f64 / segments as f64;
+            Vec3d::new(phi.cos(), 0.0, phi.sin())
+        };
+        let point = |sector: usize, y: f64| {
+            let r = radial(sector);
+            Vec4d::new(radius * r.x(), y, radius * r.z(), 1.0)
+        };
+
+        let mut triangles = Vec::with_capacity(if capped { 4 * segments } else { 2 * segments });
+        for sector in 0..segments {
+            let (a, b) = (point(sector, half), point(sector + 1, half));
+            let (c, d) = (point(sector + 1, -half), point(sector, -half));
+            let (na, nb) = (radial(sector), radial(sector + 1));
+
+            let mut upper = Triangle::new(color, a, b, c);
+            *upper.vertex_normals_mut() = Some([na, nb, nb]);
+            let mut lower = Triangle::new(color, a, c, d);
+            *lower.vertex_normals_mut() = Some([na, nb, na]);
+            triangles.push(upper);
+            triangles.push(lower);
+        }
+
+        if capped {
+            for (y, normal) in [(half, Vec3d::new(0.0, 1.0, 0.0)), (-half, Vec3d::new(0.0, -1.0, 0.0))] {
+                let center = Vec4d::new(0.0, y, 0.0, 1.0);
+                for sector in 0..segments {
+                    let (p1, p2) = (point(sector, y), point(sector + 1, y));
+                    let mut cap = if y > 0.0 {
+                        Triangle::new(color, center, p2, p1)
+                    } else {
+                        Triangle::new(color, center, p1, p2)
+                    };
+                    *cap.vertex_normals_mut() = Some([normal, normal, normal]);
+                    triangles.push(cap);
+                }
+            }
+        }
+
+        Cylinder {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles,
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+pub struct PlaneMesh {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for PlaneMesh {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for PlaneMesh {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl PlaneMesh {
+    // Flat width x depth rectangle in the XZ plane, centered at the origin and facing +Y,
+    // split into x_segments x z_segments quads (at least one each) of two triangles.
+    pub fn new(
+        nametag: ObjectNameTag,
+        width: f64,
+        depth: f64,
+        x_segments: usize,
+        z_segments: usize,
+        color: Color,
+    ) -> PlaneMesh {
+        let (x_segments, z_segments) = (x_segments.max(1), z_segments.max(1));
+        let point = |i: usize, j: usize| {
+            Vec4d::new(
+                width * (i as f64 / x_segments as f64 - 0.5),
+                0.0,
+                depth * (j as f64 / z_segments as f64 - 0.5),
+                1.0,
+            )
+        };
+
+        let mut triangles = Vec::with_capacity(2 * x_segments * z_segments);
+        for j in 0..z_segments {
+            for i in 0..x_segments {
+                let (a, b, c, d) = (point(i, j), point(i, j + 1), point(i + 1, j + 1), point(i + 1, j));
+                triangles.push(Triangle::new(color, a, b, c));
+                triangles.push(Triangle::new(color, a, c, d));
+            }
+        }
+
+        PlaneMesh {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles,
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+pub struct Cone {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Cone {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Cone {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Cone {
+    // Cone around the y axis, centered at the origin: base circle at -height / 2 closed by a cap facing -Y,
+    // apex at +height / 2, with segments (at least 3) side triangles. smooth stores the true cone normals
+    // as vertex normals of the sides, the apex gets the normal of the middle of its segment.
+    pub fn new(
+        nametag: ObjectNameTag,
+        radius: f64,
+        height: f64,
+        segments: usize,
+        smooth: bool,
+        color: Color,
+    ) -> Cone {
+        let segments = segments.max(3);
+        let half = 0.5 * height;
+        let angle = |sector: f64| 2.0 * PI * sector / segments as f64;
+        let point = |sector: usize| {
+            let phi = angle(sector as f64);
+            Vec4d::new(radius * phi.cos(), -half, radius * phi.sin(), 1.0)
+        };
+        // the slant normal tilts up from the radial direction by as much as the side leans in
+        let normal = |phi: f64| Vec3d::new(height * phi.cos(), radius, height * phi.sin()).normalized();
+
+        let apex = Vec4d::new(0.0, half, 0.0, 1.0);
+        let center = Vec4d::new(0.0, -half, 0.0, 1.0);
+        let mut triangles = Vec::with_capacity(2 * segments);
+        for sector in 0..segments {
+            let mut side = Triangle::new(color, apex, point(sector + 1), point(sector));
+            if smooth {
+                let (s, t) = (sector as f64, sector as f64 + 1.0);
+                *side.vertex_normals_mut() = Some([normal(angle(s + 0.5)), normal(angle(t)), normal(angle(s))]);
+            }
+            triangles.push(side);
+        }
+        for sector in 0..segments {
+            triangles.push(Triangle::new(color, center, point(sector), point(sector + 1)));
+        }
+
+        Cone {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles,
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+// Triangles of a convex regular polyhedron given by its vertices: faces are the vertex triples that are
+// pairwise one edge apart, wound to face outward. Scaled to an edge length of size and centered at the
+// origin, like Cube.
+fn regular_polyhedron(vertices: &[Vec3d], size: f64, color: Color) -> Vec<Triangle> {
+    let center = vertices.iter().fold(Vec3d::new(0.0, 0.0, 0.0), |sum, v| sum + *v) / vertices.len() as f64;
+    let distance = |a: usize, b: usize| (vertices[a] - vertices[b]).abs();
+    let edge = (1..vertices.len()).map(|b| distance(0, b)).fold(f64::INFINITY, f64::min);
+    let is_edge = |a: usize, b: usize| (distance(a, b) - edge).abs() < 1e-9 * edge;
+    let point = |i: usize| ((vertices[i] - center) * (size / edge)).make_point_4d();
+
+    let n = vertices.len();
+    let mut triangles = vec![];
+    for a in 0..n {
+        for b in a + 1..n {
+            for c in b + 1..n {
+                if !(is_edge(a, b) && is_edge(b, c) && is_edge(a, c)) {
+                    continue;
+                }
+                let tri = Triangle::new(color, point(a), point(b), point(c));
+                if tri.normal().dot(&tri.position()) < 0.0 {
+                    triangles.push(Triangle::new(color, point(a), point(c), point(b)));
+                } else {
+                    triangles.push(tri);
+                }
+            }
+        }
+    }
+
+    triangles
+}
+
+pub struct Tetrahedron {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Tetrahedron {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Tetrahedron {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Tetrahedron {
+    // regular tetrahedron with 4 vertices and 4 faces, edge length size, centered at the origin
+    pub fn new(nametag: ObjectNameTag, size: f64, color: Color) -> Tetrahedron {
+        let vertices = [
+            Vec3d::new(1.0, 1.0, 1.0),
+            Vec3d::new(1.0, -1.0, -1.0),
+            Vec3d::new(-1.0, 1.0, -1.0),
+            Vec3d::new(-1.0, -1.0, 1.0),
+        ];
+
+        Tetrahedron {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles: regular_polyhedron(&vertices, size, color),
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+pub struct Octahedron {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Octahedron {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Octahedron {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Octahedron {
+    // regular octahedron with 6 vertices on the axes and 8 faces, edge length size, centered at the origin
+    pub fn new(nametag: ObjectNameTag, size: f64, color: Color) -> Octahedron {
+        let vertices = [
+            Vec3d::new(1.0, 0.0, 0.0),
+            Vec3d::new(-1.0, 0.0, 0.0),
+            Vec3d::new(0.0, 1.0, 0.0),
+            Vec3d::new(0.0, -1.0, 0.0),
+            Vec3d::new(0.0, 0.0, 1.0),
+            Vec3d::new(0.0, 0.0, -1.0),
+        ];
+
+        Octahedron {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles: regular_polyhedron(&vertices, size, color),
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+pub struct Icosahedron {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl Object for Icosahedron {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for Icosahedron {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+impl Icosahedron {
+    // regular icosahedron with 12 vertices and 20 faces, edge length size, centered at the origin.
+    // Subdivided and pushed out to a sphere it gives a geodesic sphere.
+    pub fn new(nametag: ObjectNameTag, size: f64, color: Color) -> Icosahedron {
+        // three orthogonal golden rectangles
+        let phi = (1.0 + 5f64.sqrt()) / 2.0;
+        let mut vertices = vec![];
+        for (a, b) in [(1.0, phi), (1.0, -phi), (-1.0, phi), (-1.0, -phi)] {
+            vertices.push(Vec3d::new(0.0, a, b));
+            vertices.push(Vec3d::new(a, b, 0.0));
+            vertices.push(Vec3d::new(b, 0.0, a));
+        }
+
+        Icosahedron {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles: regular_polyhedron(&vertices, size, color),
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+}
+
+// Mesh built from an arbitrary list of triangles (loaded from files, split from other meshes).
+pub struct LoadedMesh {
+    obj: ObjectStruct,
+    color: Color,
+    triangles: Vec<Triangle>,
+    visible: bool,
+    materials: Vec<Material>,
+    group_order: Vec<u32>,
+    geometry: GeometryCache,
+}
+
+impl LoadedMesh {
+    pub fn new(nametag: ObjectNameTag, triangles: Vec<Triangle>, color: Color) -> LoadedMesh {
+        LoadedMesh {
+            obj: ObjectStruct::new(nametag),
+            color,
+            triangles,
+            visible: true,
+            materials: vec![],
+            group_order: vec![],
+            geometry: GeometryCache::default(),
+        }
+    }
+
+    // Closed tube of the given radius around the polyline, with segments vertices per ring
+    // and flat caps at both ends. Empty for less than two distinct points.
+    pub fn tube(nametag: ObjectNameTag, points: &[Vec3d], radius: f64, segments: usize, color: Color) -> LoadedMesh {
+        let frames = polyline_frames(points);
+        let mut triangles = vec![];
+
+        if frames.len() >= 2 && segments >= 3 {
+            let rings: Vec<Vec<Vec4d>> = frames
+                .iter()
+                .map(|(center, _, normal, binormal)| {
+                    (0..segments)
+                        .map(|j| {
+                            let angle = 2.0 * PI * j as f64 / segments as f64;
+                            (center + &((normal * angle.cos() + binormal * angle.sin()) * radius)).make_point_4d()
+                        })
+                        .collect()
+                })
+                .collect();
+
+            for ring in rings.windows(2) {
+                for j in 0..segments {
+                    let k = (j + 1) % segments;
+                    let (a, b, c, d) = (&ring[0][j], &ring[0][k], &ring[1][k], &ring[1][j]);
+                    triangles.push(Triangle::new(color, *a, *b, *c));
+                    triangles.push(Triangle::new(color, *a, *c, *d));
+                }
+            }
+
+            let (first, last) = (frames[0].0.make_point_4d(), frames[frames.len() - 1].0.make_point_4d());
+            let (start, end) = (&rings[0], &rings[rings.len() - 1]);
+            for j in 0..segments {
+                let k = (j + 1) % segments;
+                triangles.push(Triangle::new(color, first, start[k], start[j]));
+                triangles.push(Triangle::new(color, last, end[j], end[k]));
+            }
+        }
+
+        LoadedMesh::new(nametag, triangles, color)
+    }
+
+    // Flat strip of the given width along the polyline, two triangles per segment.
+    pub fn ribbon(nametag: ObjectNameTag, points: &[Vec3d], width: f64, color: Color) -> LoadedMesh {
+        let frames = polyline_frames(points);
+        let edges: Vec<(Vec4d, Vec4d)> = frames
+            .iter()
+            .map(|(center, _, normal, _)| {
+                let side = normal * (0.5 * width);
+                ((center + &side).make_point_4d(), (center - &side).make_point_4d())
+            })
+            .collect();
+
+        let mut triangles = vec![];
+        for pair in edges.windows(2) {
+            let ((l0, r0), (l1, r1)) = (&pair[0], &pair[1]);
+            triangles.push(Triangle::new(color, *l0, *r0, *r1));
+            triangles.push(Triangle::new(color, *l0, *r1, *l1));
+        }
+
+        LoadedMesh::new(nametag, triangles, color)
+    }
+}
+
+// (point, tangent, normal, binormal) along a polyline with repeated points removed. The normal is
+// carried over from point to point (parallel transport), so tubes don't twist around the path.
+fn polyline_frames(points: &[Vec3d]) -> Vec<(Vec3d, Vec3d, Vec3d, Vec3d)> {
+    let mut path: Vec<&Vec3d> = vec![];
+    for p in points {
+        if path.last().is_none_or(|last| (*last - p).abs() > f64::EPSILON) {
+            path.push(p);
+        }
+    }
+    if path.len() < 2 {
+        return vec![];
+    }
+
+    let mut frames: Vec<(Vec3d, Vec3d, Vec3d, Vec3d)> = Vec::with_capacity(path.len());
+    for i in 0..path.len() {
+        let (prev, next) = (path[i.saturating_sub(1)], path[(i + 1).min(path.len() - 1)]);
+        let tangent = (next - prev).normalized();
+
+        let guess = match frames.last() {
+            Some((_, _, normal, _)) => *normal,
+            None if tangent.x().abs() < 0.9 => Vec3d::new(1.0, 0.0, 0.0),
+            None => Vec3d::new(0.0, 1.0, 0.0),
+        };
+        let normal = (guess - (tangent * guess.dot(&tangent))).normalized();
+        let binormal = tangent.cross(&normal);
+
+        frames.push((*path[i], tangent, normal, binormal));
+    }
+
+    frames
+}
+
+impl Object for LoadedMesh {
+    fn nametag(&self) -> &ObjectNameTag {
+        &self.obj.nametag
+    }
+    fn nametag_mut(&mut self) -> &mut ObjectNameTag {
+        &mut self.obj.nametag
+    }
+    fn transform_matrix(&self) -> &Matrix4x4 {
+        &self.obj.transform
+    }
+    fn transform_matrix_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.obj.transform
+    }
+    fn position(&self) -> &Vec3d {
+        &self.obj.position
+    }
+    fn position_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.position
+    }
+    fn angle(&self) -> &Vec3d {
+        &self.obj.angle
+    }
+    fn angle_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle
+    }
+    fn angle_left_up_look_at(&self) -> &Vec3d {
+        &self.obj.angle_left_up_look_at
+    }
+    fn angle_left_up_look_at_mut(&mut self) -> &mut Vec3d {
+        &mut self.obj.angle_left_up_look_at
+    }
+    fn attached_objects(&self) -> &HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &self.obj.attached_objects
+    }
+    fn attached_objects_mut(&mut self) -> &mut HashMap<ObjectNameTag, rc::Weak<RefCell<dyn Object>>> {
+        &mut self.obj.attached_objects
+    }
+    fn as_mesh(&self) -> Option<&dyn Mesh> {
+        Some(self)
+    }
+    fn as_mesh_mut(&mut self) -> Option<&mut dyn Mesh> {
+        Some(self)
+    }
+}
+
+impl Mesh for LoadedMesh {
+    fn color(&self) -> Color {
+        self.color
+    }
+    fn color_mut(&mut self) -> &mut Color {
+        &mut self.color
+    }
+    fn triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    fn triangles_mut(&mut self) -> &mut Vec<Triangle> {
+        self.geometry.invalidate();
+        &mut self.triangles
+    }
+    fn visible(&self) -> bool {
+        self.visible
+    }
+    fn visible_mut(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+    fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+    fn group_order(&self) -> &Vec<u32> {
+        &self.group_order
+    }
+    fn group_order_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.group_order
+    }
+    fn geometry_cache(&self) -> &GeometryCache {
+        &self.geometry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::f64::consts::PI;
+    use std::rc::Rc;
+
+    use macroquad::prelude::{Color, WHITE};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use crate::adjacency::vertex_key;
+    use crate::math::plane::Plane;
+    use crate::math::quaternion::Quaternion;
+    use crate::math::vec_3d::Vec3d;
+    use crate::math::vec_4d::Vec4d;
+    use crate::triangle::Triangle;
+
+    use crate::math::matrix4x4::Matrix4x4;
+
+    use super::{
+        Cone, Cube, Cylinder, Icosahedron, LoadedMesh, Mesh, Object, ObjectNameTag, Octahedron, PlaneMesh, Pose, Sphere,
+        Tetrahedron,
+    };
+
+    fn cube() -> Cube {
+        Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE)
+    }
+
+    #[test]
+    fn smooth_normals_keep_cube_edges_sharp() {
+        let mut cube = cube();
+        cube.compute_smooth_normals(PI / 6.0);
+
+        for tri in cube.triangles() {
+            for n in tri.vertex_normals().unwrap() {
+                assert_eq!(*n, tri.normal());
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_normals_average_shallow_fold() {
+        // two triangles sharing the edge on the x axis, bent by 10 degrees
+        let bend = 10.0_f64.to_radians();
+        let mut mesh = cube();
+        mesh.triangles = vec![
+            Triangle::new(
+                WHITE,
+                Vec4d::new(0.0, 0.0, 0.0, 1.0),
+                Vec4d::new(0.0, 0.0, 1.0, 1.0),
+                Vec4d::new(1.0, 0.0, 0.0, 1.0),
+            ),
+            Triangle::new(
+                WHITE,
+                Vec4d::new(0.0, 0.0, 0.0, 1.0),
+                Vec4d::new(1.0, 0.0, 0.0, 1.0),
+                Vec4d::new(0.0, -bend.sin(), -bend.cos(), 1.0),
+            ),
+        ];
+        mesh.compute_smooth_normals(PI / 6.0);
+
+        let tri = &mesh.triangles()[0];
+        let normals = tri.vertex_normals().unwrap();
+        // shared vertices (0 and 2) are averaged, the free one keeps the face normal
+        assert!(normals[0] != tri.normal() && normals[2] != tri.normal());
+        assert_eq!(normals[1], tri.normal());
+        assert_eq!(normals[0], (tri.normal() + mesh.triangles()[1].normal()).normalized());
+        assert_eq!(mesh.triangles()[1].vertex_normals().unwrap()[0], normals[0]);
+    }
+
+    #[test]
+    fn lerp_pose() {
+        let up = Vec3d::new(0.0, 1.0, 0.0);
+        let a = Pose::new(Vec3d::new(0.0, 0.0, 0.0), Quaternion::identity(), Vec3d::new(1.0, 1.0, 1.0));
+        let b = Pose::new(
+            Vec3d::new(4.0, 2.0, -2.0),
+            Quaternion::from_axis_angle(&up, PI / 2.0),
+            Vec3d::new(3.0, 3.0, 3.0),
+        );
+        let mut cube = cube();
+
+        cube.lerp_pose(&a, &b, 0.0);
+        let pose = cube.pose();
+        assert_eq!(pose.position, a.position);
+        assert!((pose.rotation.dot(&a.rotation).abs() - 1.0).abs() < 1e-9);
+        assert_eq!(pose.scale, a.scale);
+
+        cube.lerp_pose(&a, &b, 1.0);
+        let pose = cube.pose();
+        assert_eq!(pose.position, b.position);
+        assert!((pose.rotation.dot(&b.rotation).abs() - 1.0).abs() < 1e-9);
+        assert_eq!(pose.scale, b.scale);
+
+        cube.lerp_pose(&a, &b, 0.5);
+        let pose = cube.pose();
+        assert_eq!(pose.position, Vec3d::new(2.0, 1.0, -1.0));
+        let half = Quaternion::from_axis_angle(&up, PI / 4.0);
+        assert!((pose.rotation.dot(&half).abs() - 1.0).abs() < 1e-9);
+        assert_eq!(pose.scale, Vec3d::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn set_pose_moves_attached_objects() {
+        let up = Vec3d::new(0.0, 1.0, 0.0);
+        let parent = Rc::new(RefCell::new(cube()));
+        let child = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Child"), 1.0, WHITE)));
+        child.borrow_mut().translate(&Vec3d::new(2.0, 0.0, 0.0));
+        parent.borrow_mut().attach(child.clone());
+
+        let turn = Quaternion::from_axis_angle(&up, PI / 2.0);
+        parent.borrow_mut().set_pose(&Pose::new(Vec3d::new(1.0, 0.0, 0.0), turn.clone(), Vec3d::new(1.0, 1.0, 1.0)));
+        assert_eq!(*child.borrow().position(), Vec3d::new(1.0, 0.0, -2.0));
+        assert_eq!(*child.borrow().transform_matrix(), turn.to_matrix());
+
+        // a flattened object still has a pose, the collapsed axis comes back from the other two
+        let flat = Pose::new(Vec3d::new(0.0, 0.0, 0.0), turn.clone(), Vec3d::new(1.0, 0.0, 1.0));
+        parent.borrow_mut().set_pose(&flat);
+        let pose = parent.borrow().pose();
+        assert_eq!(pose.scale, flat.scale);
+        assert!((pose.rotation.dot(&turn).abs() - 1.0).abs() < 1e-9);
+        // and can be scaled back up, attached objects only follow the position then
+        parent.borrow_mut().set_pose(&Pose::new(Vec3d::new(0.0, 0.0, 0.0), turn, Vec3d::new(1.0, 1.0, 1.0)));
+        assert!(!child.borrow().transform_matrix().x().x().is_nan());
+    }
+
+    #[test]
+    fn bounding_sphere() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(1.0, 2.0, 3.0));
+        let (center, radius) = cube.bounding_sphere();
+        assert_eq!(center, Vec3d::new(1.0, 2.0, 3.0));
+        for tri in cube.world_triangles() {
+            for p in tri.points() {
+                assert!((Vec3d::from_vec4d(p) - center).abs() <= radius + 1e-12);
+            }
+        }
+        // the corners of the cube are all at the radius
+        assert!((radius - Vec3d::from_vec4d(&cube.triangles()[0].points()[0]).abs()).abs() < 1e-12);
+
+        // the local sphere is kept, scaling the cube only scales the radius
+        cube.scale(&Vec3d::new(1.0, 3.0, 1.0));
+        assert_eq!(cube.bounding_sphere(), (center, radius * 3.0));
+        // changing the triangles drops it
+        *cube.triangles_mut() = cube.triangles()[..2].to_vec();
+        assert!(cube.local_bounding_sphere().1 < radius);
+
+        let mut empty = LoadedMesh::new(ObjectNameTag::new("Empty"), vec![], WHITE);
+        empty.translate(&Vec3d::new(0.0, 4.0, 0.0));
+        assert_eq!(empty.bounding_sphere(), (Vec3d::new(0.0, 4.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn descendants() {
+        let cube = |name: &str| Rc::new(RefCell::new(Cube::new(ObjectNameTag::new(name), 1.0, WHITE)));
+        let (root, arm, hand, leg) = (cube("Root"), cube("Arm"), cube("Hand"), cube("Leg"));
+        arm.borrow_mut().attach(hand.clone());
+        root.borrow_mut().attach(arm.clone());
+        root.borrow_mut().attach(leg.clone());
+
+        // breadth first: both children before the grandchild
+        let names = |root: &Rc<RefCell<Cube>>| -> Vec<String> {
+            root.borrow().descendants().iter().map(|o| o.borrow().nametag().name().to_string()).collect()
+        };
+        let found = names(&root);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[2], "Hand");
+
+        // only weak references are kept, a dropped child is skipped along with its subtree
+        drop(arm);
+        assert_eq!(names(&root), ["Leg"]);
+        assert!(hand.borrow().descendants().is_empty());
+    }
+
+    #[test]
+    fn signed_distance() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        cube.translate(&Vec3d::new(1.0, 0.0, 0.0));
+
+        assert!((cube.signed_distance(&Vec3d::new(1.0, 0.0, -3.0)) - 2.0).abs() < 1e-9);
+        assert!(cube.signed_distance(&Vec3d::new(1.0, 0.0, -1.0)).abs() < 1e-9);
+        assert!((cube.signed_distance(&Vec3d::new(1.0, 0.0, 0.0)) + 1.0).abs() < 1e-9);
+        assert!((cube.signed_distance(&Vec3d::new(1.5, 0.2, 0.5)) + 0.5).abs() < 1e-9);
+        // nearest to the corner
+        assert!((cube.signed_distance(&Vec3d::new(3.0, 2.0, 2.0)) - 3.0_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn split_connected() {
+        let shift = Matrix4x4::translation(&Vec3d::new(5.0, 0.0, 0.0));
+        let mut triangles = cube().triangles().clone();
+        triangles.extend(cube().triangles().iter().map(|t| t * &shift));
+
+        let mesh = LoadedMesh::new(ObjectNameTag::new("Merged"), triangles, WHITE);
+        let components = mesh.split_connected();
+
+        assert_eq!(components.len(), 2);
+        assert!(components.iter().all(|c| c.triangles().len() == 12));
+        assert!(components[1].triangles().iter().all(|t| t.position().x() > 4.0));
+    }
+
+    #[test]
+    fn cube_adjacency_is_closed_manifold() {
+        let mut cube = cube();
+        let adjacency = cube.adjacency();
+
+        assert_eq!(adjacency.edges().len(), 18);
+        assert!(adjacency.edges().values().all(|tris| tris.len() == 2));
+        assert!((0..12).all(|i| adjacency.neighbors(i).len() == 3));
+
+        assert!(Rc::ptr_eq(&adjacency, &cube.adjacency()));
+        cube.triangles_mut().pop();
+        let rebuilt = cube.adjacency();
+        assert!(!Rc::ptr_eq(&adjacency, &rebuilt));
+        assert_eq!(rebuilt.edges().values().filter(|tris| tris.len() == 1).count(), 3);
+    }
+
+    #[test]
+    fn mirrored_cube_faces_point_outward() {
+        let mut cube = cube();
+        cube.scale(&Vec3d::new(-1.0, 1.0, 1.0));
+
+        let triangles = cube.world_triangles();
+        let center = triangles.iter().fold(Vec3d::new(0.0, 0.0, 0.0), |sum, t| sum + t.position()) / 12.0;
+        for tri in triangles.iter() {
+            assert!(tri.normal().dot(&(tri.position() - center)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn new_colored_faces() {
+        let colors = [
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            Color::new(0.0, 1.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 1.0, 1.0),
+            Color::new(1.0, 1.0, 0.0, 1.0),
+            Color::new(0.0, 1.0, 1.0, 1.0),
+            Color::new(1.0, 0.0, 1.0, 1.0),
+        ];
+        let cube = Cube::new_colored(ObjectNameTag::new("Dice"), 2.0, colors);
+        let axes = [
+            Vec3d::new(1.0, 0.0, 0.0),
+            Vec3d::new(-1.0, 0.0, 0.0),
+            Vec3d::new(0.0, 1.0, 0.0),
+            Vec3d::new(0.0, -1.0, 0.0),
+            Vec3d::new(0.0, 0.0, 1.0),
+            Vec3d::new(0.0, 0.0, -1.0),
+        ];
+
+        let face = |axis: &Vec3d| -> Vec<Color> {
+            cube.triangles().iter().filter(|t| t.normal() == *axis).map(|t| t.color()).collect()
+        };
+
+        for (axis, color) in axes.iter().zip(colors) {
+            assert_eq!(face(axis), vec![color, color]);
+        }
+        // opposite faces come in pairs
+        for i in (0..6).step_by(2) {
+            assert_ne!(face(&axes[i]), face(&axes[i + 1]));
+        }
+    }
+
+    #[test]
+    fn normalize_to_unit() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 10.0, WHITE);
+        cube *= &Matrix4x4::translation(&Vec3d::new(20.0, 5.0, -3.0));
+        let original = cube.triangles().clone();
+
+        let m = cube.normalize_to_unit();
+        let (min, max) = cube.local_aabb().unwrap();
+        assert_eq!(min, Vec3d::new(-0.5, -0.5, -0.5));
+        assert_eq!(max, Vec3d::new(0.5, 0.5, 0.5));
+
+        let inverse = m.inverse().unwrap();
+        for (tri, orig) in cube.triangles().iter().zip(original.iter()) {
+            assert_eq!((tri * &inverse).points(), orig.points());
+        }
+    }
+
+    #[test]
+    fn bake_transform() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.rotate(&Vec3d::new(0.3, 0.7, 0.0));
+        cube.translate(&Vec3d::new(4.0, -2.0, 1.0));
+        let world = cube.world_triangles();
+
+        cube.bake_transform();
+        assert_eq!(cube.model(), Matrix4x4::identity());
+        assert_eq!(*cube.position(), Vec3d::new(0.0, 0.0, 0.0));
+        for (tri, expected) in cube.triangles().iter().zip(world.iter()) {
+            assert_eq!(tri.points(), expected.points());
+        }
+        assert!((cube.triangles()[0].position().x() - 4.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn rotate_quat() {
+        let axis = Vec3d::new(1.0, -2.0, 0.5).normalized();
+        let mut by_quat = Cube::new(ObjectNameTag::new("Quat"), 1.0, WHITE);
+        let mut by_axis = Cube::new(ObjectNameTag::new("Axis"), 1.0, WHITE);
+        by_quat.rotate(&Vec3d::new(0.3, 0.0, -0.2));
+        by_axis.rotate(&Vec3d::new(0.3, 0.0, -0.2));
+
+        by_quat.rotate_quat(&Quaternion::from_axis_angle(&axis, 0.7));
+        by_axis.rotate_around_vec(&axis, 0.7);
+        assert_eq!(by_quat.model(), by_axis.model());
+
+        // two steps or one composed rotation, the length of q doesn't matter
+        let quarter = Quaternion::from_axis_angle(&axis, PI / 4.0);
+        let twice = Quaternion::new(2.0 * quarter.x(), 2.0 * quarter.y(), 2.0 * quarter.z(), 2.0 * quarter.w());
+        by_quat.rotate_quat(&twice);
+        by_quat.rotate_quat(&quarter);
+        by_axis.rotate_quat(&(&quarter * &quarter));
+        assert_eq!(by_quat.model(), by_axis.model());
+    }
+
+    #[test]
+    fn orthonormalize_drifted_rotation() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        cube.translate(&Vec3d::new(4.0, -2.0, 1.0));
+        let axis = Vec3d::new(1.0, 2.0, 3.0).normalized();
+        for i in 0..5000 {
+            cube.rotate_around_vec(&axis, 1e-3 * (i % 7) as f64);
+        }
+
+        cube.orthonormalize();
+        let m = cube.transform_matrix();
+        for (a, b) in [(m.x(), m.y()), (m.y(), m.z()), (m.z(), m.x())] {
+            assert!((a.abs() - 1.0).abs() < 1e-12);
+            assert!(a.dot(&b).abs() < 1e-12);
+        }
+        assert_eq!(*cube.position(), Vec3d::new(4.0, -2.0, 1.0));
+    }
+
+    #[test]
+    fn sphere() {
+        let (rings, sectors) = (6, 8);
+        let sphere = Sphere::new(ObjectNameTag::new("Sphere"), 2.0, rings, sectors, WHITE);
+        // one triangle per sector collapses at each pole
+        assert_eq!(sphere.triangles().len(), 2 * sectors * (rings - 1) - 2 * sectors);
+
+        for tri in sphere.triangles() {
+            assert!(tri.area() > 1e-6);
+            assert!(tri.normal().dot(&tri.position()) > 0.0);
+            for p in tri.points() {
+                assert!((Vec3d::from_vec4d(p).abs() - 2.0).abs() < 1e-12);
+            }
+        }
+        assert!(sphere.signed_distance(&Vec3d::new(0.0, 0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn cylinder() {
+        let segments = 10;
+        let open = Cylinder::new(ObjectNameTag::new("Pipe"), 1.0, 2.0, segments, false, WHITE);
+        assert_eq!(open.triangles().len(), 2 * segments);
+        assert!(open.adjacency().edges().values().any(|tris| tris.len() == 1));
+
+        let closed = Cylinder::new(ObjectNameTag::new("Can"), 1.0, 2.0, segments, true, WHITE);
+        assert_eq!(closed.triangles().len(), 4 * segments);
+        // watertight: every edge between exactly two triangles
+        assert!(closed.adjacency().edges().values().all(|tris| tris.len() == 2));
+
+        for tri in closed.triangles() {
+            let c = tri.position();
+            let outward = if c.y().abs() > 0.999 { Vec3d::new(0.0, c.y(), 0.0) } else { Vec3d::new(c.x(), 0.0, c.z()) };
+            assert!(tri.normal().dot(&outward.normalized()) > 0.9);
+            for (p, n) in tri.points().iter().zip(tri.vertex_normals().unwrap()) {
+                assert!((n.abs() - 1.0).abs() < 1e-12);
+                assert!(n.dot(&Vec3d::from_vec4d(p)) > 0.0);
+            }
+        }
+        assert!(closed.signed_distance(&Vec3d::new(0.0, 0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn cone() {
+        let segments = 12;
+        let cone = Cone::new(ObjectNameTag::new("Tree"), 1.0, 2.0, segments, true, WHITE);
+        assert_eq!(cone.triangles().len(), 2 * segments);
+        assert!(cone.adjacency().edges().values().all(|tris| tris.len() == 2));
+
+        let apex = Vec3d::new(0.0, 1.0, 0.0);
+        let at_apex = cone.triangles().iter().filter(|t| t.points().iter().any(|p| Vec3d::from_vec4d(p) == apex));
+        assert_eq!(at_apex.count(), segments);
+
+        // radius 1 and height 2: the slant normal is (2 cos, 1, 2 sin) / sqrt(5)
+        for tri in cone.triangles().iter().take(segments) {
+            assert!(tri.normal().y() > 0.0);
+            for (p, n) in tri.points().iter().zip(tri.vertex_normals().unwrap()) {
+                assert!((n.y() - 1.0 / 5f64.sqrt()).abs() < 1e-12);
+                if p.y() < 0.0 {
+                    assert!((*n - Vec3d::new(2.0 * p.x(), 1.0, 2.0 * p.z()).normalized()).abs() < 1e-12);
+                }
+            }
+        }
+        for tri in cone.triangles().iter().skip(segments) {
+            assert_eq!(tri.normal(), Vec3d::new(0.0, -1.0, 0.0));
+        }
+        assert!(cone.signed_distance(&Vec3d::new(0.0, 0.0, 0.0)) < 0.0);
+
+        let flat = Cone::new(ObjectNameTag::new("Marker"), 1.0, 2.0, segments, false, WHITE);
+        assert!(flat.triangles().iter().all(|t| t.vertex_normals().is_none()));
+    }
+
+    #[test]
+    fn platonic_solids() {
+        let size = 2.0;
+        let tetrahedron = Tetrahedron::new(ObjectNameTag::new("Tetrahedron"), size, WHITE);
+        let octahedron = Octahedron::new(ObjectNameTag::new("Octahedron"), size, WHITE);
+        let icosahedron = Icosahedron::new(ObjectNameTag::new("Icosahedron"), size, WHITE);
+
+        for (mesh, vertices, faces) in [
+            (&tetrahedron as &dyn Mesh, 4, 4),
+            (&octahedron as &dyn Mesh, 6, 8),
+            (&icosahedron as &dyn Mesh, 12, 20),
+        ] {
+            assert_eq!(mesh.triangles().len(), faces);
+            let unique: HashSet<_> = mesh.triangles().iter().flat_map(|t| t.points().iter().map(vertex_key)).collect();
+            assert_eq!(unique.len(), vertices);
+
+            // closed, Euler's formula gives the edge count, all of them size long
+            assert!(mesh.adjacency().edges().values().all(|tris| tris.len() == 2));
+            let edges = mesh.unique_edges();
+            assert_eq!(edges.len(), vertices + faces - 2);
+            assert!(edges.iter().all(|(a, b)| ((*b - *a).abs() - size).abs() < 1e-9));
+            for tri in mesh.triangles() {
+                assert!(tri.normal().dot(&tri.position().normalized()) > 0.5);
+            }
+            assert!(mesh.signed_distance(&Vec3d::new(0.0, 0.0, 0.0)) < 0.0);
+        }
+    }
+
+    #[test]
+    fn geodesic_sphere() {
+        let mut sphere = Icosahedron::new(ObjectNameTag::new("Geodesic"), 1.0, WHITE);
+        sphere.subdivide(1);
+        assert_eq!(sphere.triangles().len(), 4 * 20);
+        // 12 corners plus one shared midpoint per edge, no cracks
+        let unique: HashSet<_> = sphere.triangles().iter().flat_map(|t| t.points().iter().map(vertex_key)).collect();
+        assert_eq!(unique.len(), 12 + 30);
+        assert!(sphere.adjacency().edges().values().all(|tris| tris.len() == 2));
+
+        sphere.subdivide(2);
+        assert_eq!(sphere.triangles().len(), 16 * 80);
+        sphere.project_to_sphere(2.0);
+        assert!(sphere.adjacency().edges().values().all(|tris| tris.len() == 2));
+        for tri in sphere.triangles() {
+            assert!(tri.normal().dot(&tri.position().normalized()) > 0.99);
+            for (p, n) in tri.points().iter().zip(tri.vertex_normals().unwrap()) {
+                assert!((Vec3d::from_vec4d(p).abs() - 2.0).abs() < 1e-12);
+                assert!((*n - Vec3d::from_vec4d(p) / 2.0).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn plane_mesh() {
+        let floor = PlaneMesh::new(ObjectNameTag::new("Floor"), 4.0, 2.0, 4, 3, WHITE);
+        assert_eq!(floor.triangles().len(), 2 * 4 * 3);
+
+        let area: f64 = floor.triangles().iter().map(|t| t.area()).sum();
+        assert!((area - 8.0).abs() < 1e-12);
+        for tri in floor.triangles() {
+            assert_eq!(tri.normal(), Vec3d::new(0.0, 1.0, 0.0));
+            for p in tri.points() {
+                assert!(p.x().abs() <= 2.0 && p.y() == 0.0 && p.z().abs() <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn tube_around_segment() {
+        let (radius, segments) = (0.5, 8);
+        let tube = LoadedMesh::tube(
+            ObjectNameTag::new("Tube"),
+            &[Vec3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 3.0)],
+            radius,
+            segments,
+            WHITE,
+        );
+        assert_eq!(tube.triangles().len(), 4 * segments);
+
+        for tri in tube.triangles() {
+            for p in tri.points() {
+                let from_axis = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                // every vertex is on a ring or a cap center
+                assert!((from_axis - radius).abs() < 1e-9 || from_axis < 1e-9);
+            }
+        }
+
+        // outward normals
+        assert!(tube.signed_distance(&Vec3d::new(0.0, 0.0, 1.5)) < 0.0);
+        assert!((tube.signed_distance(&Vec3d::new(2.0, 0.0, 1.5)) - 1.5).abs() < 0.1);
+
+        let ribbon = LoadedMesh::ribbon(
+            ObjectNameTag::new("Ribbon"),
+            &[Vec3d::new(0.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0), Vec3d::new(1.0, 1.0, 0.0)],
+            0.2,
+            WHITE,
+        );
+        assert_eq!(ribbon.triangles().len(), 4);
+    }
+
+    #[test]
+    fn weld_triangle_soup() {
+        // cube triangles with every vertex nudged a little off its corner
+        let cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        let mut k = 0.0;
+        let mut nudge = |p: &Vec4d| {
+            k += 1.0;
+            Vec4d::new(p.x() + 1e-7 * k, p.y() - 5e-8 * k, p.z(), 1.0)
+        };
+        let mut soup: Vec<Triangle> = cube
+            .triangles()
+            .iter()
+            .map(|t| {
+                let p = t.points();
+                Triangle::new(WHITE, nudge(&p[0]), nudge(&p[1]), nudge(&p[2]))
+            })
+            .collect();
+        // a sliver away from the cube, two of its corners weld together and it collapses
+        soup.push(Triangle::new(
+            WHITE,
+            Vec4d::new(10.0, 0.0, 0.0, 1.0),
+            Vec4d::new(10.0 + 1e-6, 0.0, 0.0, 1.0),
+            Vec4d::new(10.0, 1.0, 0.0, 1.0),
+        ));
+        let mut mesh = LoadedMesh::new(ObjectNameTag::new("Soup"), soup, WHITE);
+
+        let distinct = |mesh: &LoadedMesh| {
+            let mut points: Vec<[f64; 3]> = vec![];
+            for p in mesh.triangles().iter().flat_map(|t| t.points().iter()) {
+                if !points.contains(&[p.x(), p.y(), p.z()]) {
+                    points.push([p.x(), p.y(), p.z()]);
+                }
+            }
+            points.len()
+        };
+        assert_eq!(distinct(&mesh), 39);
+
+        // the sliver's positions are gone with it
+        assert_eq!(mesh.weld_vertices(1e-4), 8);
+        assert_eq!(mesh.triangles().len(), 12);
+        assert_eq!(distinct(&mesh), 8);
+
+        // too coarse a tolerance collapses the whole cube
+        assert_eq!(mesh.weld_vertices(2.0), 0);
+        assert!(mesh.triangles().is_empty());
+    }
+
+    #[test]
+    fn sample_surface() {
+        let big = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(2.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 2.0, 0.0, 1.0),
+        );
+        let small = Triangle::new(
+            WHITE,
+            Vec4d::new(5.0, 0.0, 0.0, 1.0),
+            Vec4d::new(5.0, 1.0, 1.0, 1.0),
+            Vec4d::new(5.0, -1.0, 1.0, 1.0),
+        );
+        assert_eq!(big.area(), 2.0 * small.area());
+        let mut mesh = LoadedMesh::new(ObjectNameTag::new("Pair"), vec![big.clone(), small.clone()], WHITE);
+        mesh.translate(&Vec3d::new(0.0, 0.0, 10.0));
+
+        let points = mesh.sample_surface(30000, &mut StdRng::seed_from_u64(7));
+        assert_eq!(points.len(), 30000);
+        let on_big = points.iter().filter(|p| p.x() < 3.0).count() as f64;
+        let on_small = points.len() as f64 - on_big;
+        assert!((on_big / on_small - 2.0).abs() < 0.1);
+
+        let [big, small] = [big, small].map(|t| Plane::from_triangle(&(&t * &mesh.model())));
+        for p in points.iter() {
+            let plane = if p.x() < 3.0 { &big } else { &small };
+            assert!(plane.distance(p).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn cross_section_of_cube() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        cube.translate(&Vec3d::new(0.0, 3.0, 0.0));
+        let plane = Plane::new(Vec3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 3.0, 0.0));
+
+        let segments = cube.cross_section(&plane);
+        // two triangles on each of the four side faces
+        assert_eq!(segments.len(), 8);
+
+        for (a, b) in segments.iter() {
+            for p in [a, b] {
+                assert!((p.y() - 3.0).abs() < 1e-9);
+                assert!((p.x().abs().max(p.z().abs()) - 1.0).abs() < 1e-9);
+            }
+        }
+
+        // closed loop: every end is shared by exactly two segments
+        for (a, _) in segments.iter() {
+            let count = segments.iter().flat_map(|(p, q)| [p, q]).filter(|p| **p == *a).count();
+            assert_eq!(count, 2);
+        }
+        let perimeter: f64 = segments.iter().map(|(a, b)| (b - a).abs()).sum();
+        assert!((perimeter - 8.0).abs() < 1e-9);
+
+        let above = Plane::new(Vec3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 5.0, 0.0));
+        assert!(cube.cross_section(&above).is_empty());
+    }
+
+    #[test]
+    fn recenter_pivot() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        cube *= &Matrix4x4::translation(&Vec3d::new(5.0, -1.0, 2.0));
+        cube.rotate(&Vec3d::new(0.2, 0.9, -0.4));
+        cube.translate(&Vec3d::new(1.0, 1.0, 1.0));
+        let world = cube.world_triangles();
+
+        assert_eq!(cube.recenter_pivot(), Vec3d::new(5.0, -1.0, 2.0));
+        let (min, max) = cube.local_aabb().unwrap();
+        assert_eq!((min + max) / 2.0, Vec3d::new(0.0, 0.0, 0.0));
+
+        for (tri, before) in cube.world_triangles().iter().zip(world.iter()) {
+            assert_eq!(tri.points(), before.points());
+        }
+    }
+
+    #[test]
+    fn make_winding_consistent() {
+        let mut cube = Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE);
+        let outward = |cube: &Cube| cube.triangles().iter().all(|t| t.normal().dot(&t.position()) > 0.0);
+        assert!(outward(&cube));
+
+        for tri in cube.triangles_mut().iter_mut().step_by(2) {
+            *tri = tri.flipped();
+        }
+        assert!(!outward(&cube));
+
+        assert_eq!(cube.make_winding_consistent(), 6);
+        assert!(outward(&cube));
+        assert_eq!(cube.make_winding_consistent(), 0);
+
+        // all reversed: consistent already, but inside out
+        for tri in cube.triangles_mut().iter_mut() {
+            *tri = tri.flipped();
+        }
+        assert_eq!(cube.make_winding_consistent(), 12);
+        assert!(outward(&cube));
+    }
+
+    #[test]
+    fn cube_unique_edges() {
+        let cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+        let edges = cube.unique_edges();
+        assert_eq!(edges.len(), 18);
+
+        let lengths: Vec<f64> = edges.iter().map(|(a, b)| (b - a).abs()).collect();
+        assert_eq!(lengths.iter().filter(|&&l| (l - 2.0).abs() < 1e-9).count(), 12);
+        assert_eq!(lengths.iter().filter(|&&l| (l - 8.0f64.sqrt()).abs() < 1e-9).count(), 6);
+    }
 }
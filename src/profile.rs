@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+// pipeline stages timed by the camera with the "profiling" feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    // model, view and projection transforms of the triangles
+    Transform,
+    // frustum and scissor clipping
+    Clip,
+    // sorting the projected triangles
+    Sort,
+    // software rasterizing (rasterize, render_to_image, ...)
+    Rasterize,
+    // whole project / sort / rasterize calls, the stages run inside of them
+    Total,
+}
+
+// Nanoseconds spent per stage since the last reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub transform_ns: u64,
+    pub clip_ns: u64,
+    pub sort_ns: u64,
+    pub rasterize_ns: u64,
+    pub total_ns: u64,
+}
+
+impl Profile {
+    pub fn add(&mut self, stage: Stage, time: Duration) {
+        let ns = time.as_nanos() as u64;
+        match stage {
+            Stage::Transform => self.transform_ns += ns,
+            Stage::Clip => self.clip_ns += ns,
+            Stage::Sort => self.sort_ns += ns,
+            Stage::Rasterize => self.rasterize_ns += ns,
+            Stage::Total => self.total_ns += ns,
+        }
+    }
+
+    // time of the four stages together, total_ns minus this is what runs between them
+    pub fn stages_ns(&self) -> u64 {
+        self.transform_ns + self.clip_ns + self.sort_ns + self.rasterize_ns
+    }
+}
+
+// Start time of a measurement, zero sized without the "profiling" feature.
+#[derive(Clone, Copy)]
+pub(crate) struct Timer {
+    #[cfg(feature = "profiling")]
+    start: std::time::Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(feature = "profiling")]
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+// Per camera accumulator, recording is a no-op without the "profiling" feature.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    #[cfg(feature = "profiling")]
+    profile: std::cell::Cell<Profile>,
+}
+
+impl Profiler {
+    #[cfg_attr(not(feature = "profiling"), allow(unused_variables))]
+    pub(crate) fn stop(&self, stage: Stage, timer: Timer) {
+        #[cfg(feature = "profiling")]
+        {
+            let mut profile = self.profile.get();
+            profile.add(stage, timer.start.elapsed());
+            self.profile.set(profile);
+        }
+    }
+
+    #[cfg(feature = "profiling")]
+    pub(crate) fn get(&self) -> Profile {
+        self.profile.get()
+    }
+
+    #[cfg(feature = "profiling")]
+    pub(crate) fn reset(&self) {
+        self.profile.set(Profile::default());
+    }
+}
@@ -0,0 +1,310 @@
+use macroquad::prelude::Color;
+
+use crate::math::vec_2d::Vec2d;
+use crate::math::vec_4d::Vec4d;
+
+// Software color + depth buffer. Points are in screen space (x right, y down, in pixels)
+// with z being the depth after the perspective divide (smaller is closer) and w being 1 / w before it.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    colors: Vec<Color>,
+    depth: Vec<f64>,
+    // only rows with y % row_step == row_offset are drawn to
+    row_step: usize,
+    row_offset: usize,
+}
+
+// lines pass the depth test when they are at most this much behind the stored depth,
+// so edges are not hidden by the faces they belong to
+const LINE_DEPTH_BIAS: f64 = 1e-5;
+
+// maps HDR colors (channels above 1) into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    // only scaled by the exposure, anything above 1 clips
+    None,
+    // c / (c + 1) after the exposure: bright colors approach 1 without reaching it
+    Reinhard,
+}
+
+impl ToneMap {
+    pub fn apply(self, color: Color, exposure: f64) -> Color {
+        let map = |c: f32| {
+            let c = c * exposure as f32;
+            match self {
+                ToneMap::None => c,
+                ToneMap::Reinhard => c / (c + 1.0),
+            }
+        };
+        Color::new(map(color.r), map(color.g), map(color.b), color.a)
+    }
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, clear_color: Color, clear_depth: f64) -> Self {
+        Self {
+            width,
+            height,
+            colors: vec![clear_color; width * height],
+            depth: vec![clear_depth; width * height],
+            row_step: 1,
+            row_offset: 0,
+        }
+    }
+
+    // Interlacing: from now on only every step-th row starting at offset is drawn to,
+    // the other rows keep what they have. Step 1 draws all rows again.
+    pub fn set_interlace(&mut self, step: usize, offset: usize) {
+        self.row_step = step.max(1);
+        self.row_offset = offset % self.row_step;
+    }
+
+    // resets the rows that are drawn to (all of them without interlacing)
+    pub fn clear_rows(&mut self, color: Color, depth: f64) {
+        for y in (self.row_offset..self.height).step_by(self.row_step) {
+            let row = y * self.width..(y + 1) * self.width;
+            self.colors[row.clone()].fill(color);
+            self.depth[row].fill(depth);
+        }
+    }
+
+    // rows in y0..y1 that are drawn to
+    fn rows(&self, y0: usize, y1: usize) -> impl Iterator<Item = usize> {
+        let first = y0 + (self.row_offset + self.row_step - y0 % self.row_step) % self.row_step;
+        (first..y1).step_by(self.row_step)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // row major, top row first
+    pub fn colors(&self) -> &Vec<Color> {
+        &self.colors
+    }
+
+    pub fn depth(&self) -> &Vec<f64> {
+        &self.depth
+    }
+
+    pub fn into_colors(self) -> Vec<Color> {
+        self.colors
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.colors[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.colors[y * self.width + x] = color;
+    }
+
+    // 8 bit RGBA pixels. With dither an ordered 4x4 Bayer threshold decides whether each channel
+    // is rounded up or down, which breaks gradient banding into a fixed (reproducible) pattern;
+    // every channel still stays within 1 of its exact value.
+    pub fn to_rgba8(&self, dither: bool) -> Vec<[u8; 4]> {
+        self.colors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let threshold = if dither {
+                    (BAYER_4X4[(i / self.width) % 4][(i % self.width) % 4] as f32 + 0.5) / 16.0
+                } else {
+                    0.5
+                };
+                [c.r, c.g, c.b, c.a].map(|v| (v.clamp(0.0, 1.0) * 255.0 + threshold).floor().min(255.0) as u8)
+            })
+            .collect()
+    }
+
+    // Depth tested fill of the pixels whose centers lie inside the triangle (any winding).
+    pub fn fill_triangle(&mut self, points: &[Vec4d; 3], color: Color) {
+        self.fill_with(points, |_| color);
+    }
+
+    // Fill with the vertex colors blended across the triangle (Gouraud shading), interpolated like the uvs
+    // of fill_textured. finish maps each blended color to the one written (tone mapping, posterizing).
+    pub fn fill_shaded(
+        &mut self,
+        points: &[Vec4d; 3],
+        colors: &[Color; 3],
+        finish: impl Fn(Color) -> Color,
+        perspective_correct: bool,
+    ) {
+        let inv_w = (*points).map(|p| if perspective_correct { p.w() } else { 1.0 });
+
+        self.fill_with(points, |bary| {
+            let weights = [bary[0] * inv_w[0], bary[1] * inv_w[1], bary[2] * inv_w[2]];
+            let sum = weights[0] + weights[1] + weights[2];
+            let blend = |channel: fn(&Color) -> f32| {
+                ((weights[0] * channel(&colors[0]) as f64
+                    + weights[1] * channel(&colors[1]) as f64
+                    + weights[2] * channel(&colors[2]) as f64)
+                    / sum) as f32
+            };
+            finish(Color::new(blend(|c| c.r), blend(|c| c.g), blend(|c| c.b), blend(|c| c.a)))
+        });
+    }
+
+    // Fill with texels sampled at interpolated uvs (see Triangle::sample_texture) and modulated by tint.
+    // Perspective correct interpolation divides the uvs by w before interpolating and multiplies back,
+    // affine interpolation is linear in screen space (cheaper, but distorts surfaces seen at an angle).
+    pub fn fill_textured(
+        &mut self,
+        points: &[Vec4d; 3],
+        uvs: &[Vec2d; 3],
+        sample: impl Fn(f64, f64) -> Color,
+        tint: Color,
+        perspective_correct: bool,
+    ) {
+        let inv_w = (*points).map(|p| if perspective_correct { p.w() } else { 1.0 });
+
+        self.fill_with(points, |bary| {
+            let weights = [bary[0] * inv_w[0], bary[1] * inv_w[1], bary[2] * inv_w[2]];
+            let sum = weights[0] + weights[1] + weights[2];
+            let u = (weights[0] * uvs[0].x() + weights[1] * uvs[1].x() + weights[2] * uvs[2].x()) / sum;
+            let v = (weights[0] * uvs[0].y() + weights[1] * uvs[1].y() + weights[2] * uvs[2].y()) / sum;
+
+            let texel = sample(u, v);
+            Color::new(texel.r * tint.r, texel.g * tint.g, texel.b * tint.b, texel.a * tint.a)
+        });
+    }
+
+    // depth tested fill, shade gets the barycentric coordinates of the pixel center
+    fn fill_with(&mut self, points: &[Vec4d; 3], mut shade: impl FnMut([f64; 3]) -> Color) {
+        let [a, b, c] = points;
+
+        let area = edge(a, b, c.x(), c.y());
+        if area == 0.0 {
+            return;
+        }
+
+        let (x0, x1) = span(a.x().min(b.x()).min(c.x()), a.x().max(b.x()).max(c.x()), self.width);
+        let (y0, y1) = span(a.y().min(b.y()).min(c.y()), a.y().max(b.y()).max(c.y()), self.height);
+
+        for y in self.rows(y0, y1) {
+            for x in x0..x1 {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+
+                let w0 = edge(b, c, px, py) / area;
+                let w1 = edge(c, a, px, py) / area;
+                let w2 = edge(a, b, px, py) / area;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let z = w0 * a.z() + w1 * b.z() + w2 * c.z();
+                let i = y * self.width + x;
+                if z < self.depth[i] {
+                    self.depth[i] = z;
+                    self.colors[i] = shade([w0, w1, w2]);
+                }
+            }
+        }
+    }
+
+    // Antialiased line of the given width in pixels: pixels on the border get partial coverage
+    // and are blended over what is already there. With depth_test the line is hidden behind
+    // closer geometry already in the buffer (hidden line removal), depth itself is not written.
+    pub fn draw_line(&mut self, a: &Vec4d, b: &Vec4d, width: f64, color: Color, depth_test: bool) {
+        let half = 0.5 * width;
+        let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+        let sqr_len = dx * dx + dy * dy;
+
+        let (x0, x1) = span(a.x().min(b.x()) - half - 1.0, a.x().max(b.x()) + half + 1.0, self.width);
+        let (y0, y1) = span(a.y().min(b.y()) - half - 1.0, a.y().max(b.y()) + half + 1.0, self.height);
+
+        for y in self.rows(y0, y1) {
+            for x in x0..x1 {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+
+                let k = if sqr_len > 0.0 {
+                    (((px - a.x()) * dx + (py - a.y()) * dy) / sqr_len).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (cx, cy) = (a.x() + dx * k, a.y() + dy * k);
+                let distance = ((px - cx) * (px - cx) + (py - cy) * (py - cy)).sqrt();
+
+                let coverage = (half + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage == 0.0 {
+                    continue;
+                }
+
+                let i = y * self.width + x;
+                let z = a.z() + (b.z() - a.z()) * k;
+                if depth_test && z > self.depth[i] + LINE_DEPTH_BIAS {
+                    continue;
+                }
+
+                let alpha = coverage as f32 * color.a;
+                let dst = self.colors[i];
+                self.colors[i] = Color::new(
+                    dst.r + (color.r - dst.r) * alpha,
+                    dst.g + (color.g - dst.g) * alpha,
+                    dst.b + (color.b - dst.b) * alpha,
+                    dst.a + (1.0 - dst.a) * alpha,
+                );
+            }
+        }
+    }
+}
+
+// pixel index range covering [min, max], clamped to the buffer
+fn span(min: f64, max: f64, size: usize) -> (usize, usize) {
+    let lo = min.floor().max(0.0) as usize;
+    let hi = (max.ceil().max(0.0) as usize).min(size);
+    (lo.min(hi), hi)
+}
+
+// twice the signed area of (a, b, p)
+fn edge(a: &Vec4d, b: &Vec4d, px: f64, py: f64) -> f64 {
+    (b.x() - a.x()) * (py - a.y()) - (b.y() - a.y()) * (px - a.x())
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::prelude::Color;
+
+    use super::FrameBuffer;
+
+    // vertical gradient spanning a few 8 bit levels over many rows
+    fn gradient() -> FrameBuffer {
+        let mut frame = FrameBuffer::new(16, 64, Color::new(0.0, 0.0, 0.0, 1.0), f64::INFINITY);
+        for y in 0..64 {
+            let v = 0.5 + y as f32 * 0.25 / 255.0;
+            for x in 0..16 {
+                frame.set_pixel(x, y, Color::new(v, v, v, 1.0));
+            }
+        }
+        frame
+    }
+
+    fn adjacent_changes(pixels: &[[u8; 4]]) -> usize {
+        (0..63).map(|y| (0..16).filter(|&x| pixels[y * 16 + x][0] != pixels[(y + 1) * 16 + x][0]).count()).sum()
+    }
+
+    #[test]
+    fn dither_breaks_banding() {
+        let frame = gradient();
+        let plain = frame.to_rgba8(false);
+        let dithered = frame.to_rgba8(true);
+
+        assert!(adjacent_changes(&dithered) > adjacent_changes(&plain));
+        assert_eq!(dithered, frame.to_rgba8(true));
+
+        for (i, p) in dithered.iter().enumerate() {
+            let exact = frame.colors()[i].r * 255.0;
+            assert!((p[0] as f32 - exact).abs() <= 1.0);
+            assert_eq!(p[3], 255);
+        }
+    }
+}
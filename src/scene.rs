@@ -0,0 +1,460 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{self, Rc};
+
+use crate::math::frustum::Frustum;
+use crate::math::matrix4x4::Matrix4x4;
+use crate::math::vec_3d::Vec3d;
+use crate::object::{Object, ObjectNameTag};
+use crate::triangle::Triangle;
+
+// Size of a scene's geometry, see Scene::stats.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SceneStats {
+    pub meshes: usize,
+    pub triangles: usize,
+    // triangles don't share vertices, so this is 3 per triangle
+    pub vertices: usize,
+    // triangle storage of the meshes, shared textures and materials not included
+    pub bytes: usize,
+}
+
+// Root objects of a scene, their attached objects are reached through them.
+#[derive(Default)]
+pub struct Scene {
+    objects: Vec<Rc<RefCell<dyn Object>>>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self { objects: vec![] }
+    }
+
+    pub fn add(&mut self, obj: Rc<RefCell<dyn Object>>) {
+        self.objects.push(obj);
+    }
+
+    pub fn objects(&self) -> &Vec<Rc<RefCell<dyn Object>>> {
+        &self.objects
+    }
+
+    // every object of the scene once: the roots and all their descendants
+    pub fn all_objects(&self) -> Vec<Rc<RefCell<dyn Object>>> {
+        let mut seen = HashSet::new();
+        let mut res = vec![];
+
+        for root in self.objects.iter() {
+            let descendants = root.borrow().descendants();
+            for obj in std::iter::once(root.clone()).chain(descendants) {
+                // by identity, different objects may share a name
+                if seen.insert(Rc::as_ptr(&obj) as *const ()) {
+                    res.push(obj);
+                }
+            }
+        }
+
+        res
+    }
+
+    // Object::set_subtree_visible on the object with this tag, false if there is none
+    pub fn set_subtree_visible(&self, tag: &ObjectNameTag, visible: bool) -> bool {
+        match self.all_objects().into_iter().find(|obj| obj.borrow().nametag() == tag) {
+            Some(obj) => {
+                obj.borrow_mut().set_subtree_visible(visible);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Rotates the named objects (roots or attached ones) together about a shared pivot, as if they
+    // were children of an object there. Objects attached to a named object follow it, so naming
+    // both a parent and its child rotates the child twice. Unknown tags are skipped.
+    pub fn rotate_group(&mut self, tags: &[ObjectNameTag], pivot: &Vec3d, r: &Vec3d) {
+        for obj in self.all_objects() {
+            if tags.contains(obj.borrow().nametag()) {
+                obj.borrow_mut().rotate_relative_point(pivot, r);
+            }
+        }
+    }
+
+    // totals over every mesh in the scene, attached ones included
+    pub fn stats(&self) -> SceneStats {
+        let mut stats = SceneStats::default();
+
+        for obj in self.all_objects() {
+            if let Some(mesh) = obj.borrow().as_mesh() {
+                let triangles = mesh.triangles();
+                stats.meshes += 1;
+                stats.triangles += triangles.len();
+                stats.vertices += 3 * triangles.len();
+                stats.bytes += triangles.capacity() * std::mem::size_of::<Triangle>();
+            }
+        }
+
+        stats
+    }
+
+    // triangles of every mesh in the scene baked into world space
+    pub fn all_world_triangles(&self) -> Vec<Triangle> {
+        let mut res = vec![];
+
+        for obj in self.all_objects() {
+            if let Some(mesh) = obj.borrow().as_mesh() {
+                res.extend(mesh.world_triangles());
+            }
+        }
+
+        res
+    }
+}
+
+type Cell = (i64, i64, i64);
+
+// Uniform grid bucketing objects by their world position,
+// so only objects in cells touched by a frustum have to be looked at.
+pub struct SpatialGrid {
+    cell_size: f64,
+    cells: HashMap<Cell, Vec<ObjectNameTag>>,
+    object_cells: HashMap<ObjectNameTag, Cell>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            object_cells: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.object_cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.object_cells.is_empty()
+    }
+
+    fn cell(&self, position: &Vec3d) -> Cell {
+        (
+            (position.x() / self.cell_size).floor() as i64,
+            (position.y() / self.cell_size).floor() as i64,
+            (position.z() / self.cell_size).floor() as i64,
+        )
+    }
+
+    // Adds the object or moves it to its new position. Only the two affected cells are touched.
+    pub fn insert(&mut self, tag: &ObjectNameTag, position: &Vec3d) {
+        let cell = self.cell(position);
+        if self.object_cells.get(tag) == Some(&cell) {
+            return;
+        }
+
+        self.remove(tag);
+        self.cells.entry(cell).or_default().push(tag.clone());
+        self.object_cells.insert(tag.clone(), cell);
+    }
+
+    pub fn remove(&mut self, tag: &ObjectNameTag) {
+        if let Some(cell) = self.object_cells.remove(tag) {
+            if let Some(tags) = self.cells.get_mut(&cell) {
+                tags.retain(|t| t != tag);
+                if tags.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    // objects in every cell the frustum touches
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<ObjectNameTag> {
+        let mut res = vec![];
+
+        for (cell, tags) in &self.cells {
+            let min = Vec3d::new(cell.0 as f64, cell.1 as f64, cell.2 as f64) * self.cell_size;
+            let max = min + Vec3d::new(self.cell_size, self.cell_size, self.cell_size);
+            if frustum.intersects_aabb(&min, &max) {
+                res.extend(tags.iter().cloned());
+            }
+        }
+
+        res
+    }
+}
+
+// Hierarchy node with a transform relative to its parent. Unlike Object::attach, moving a parent
+// doesn't touch its children: world transforms are composed up the parent chain when asked for.
+pub struct TransformNode {
+    nametag: ObjectNameTag,
+    local: Matrix4x4,
+    parent: Option<rc::Weak<RefCell<TransformNode>>>,
+}
+
+impl TransformNode {
+    pub fn new(nametag: ObjectNameTag, local: Matrix4x4) -> Rc<RefCell<TransformNode>> {
+        Rc::new(RefCell::new(TransformNode {
+            nametag,
+            local,
+            parent: None,
+        }))
+    }
+
+    pub fn nametag(&self) -> &ObjectNameTag {
+        &self.nametag
+    }
+
+    pub fn local(&self) -> &Matrix4x4 {
+        &self.local
+    }
+    pub fn local_mut(&mut self) -> &mut Matrix4x4 {
+        &mut self.local
+    }
+
+    pub fn parent(&self) -> Option<Rc<RefCell<TransformNode>>> {
+        self.parent.as_ref().and_then(|p| p.upgrade())
+    }
+
+    // None detaches the node, making it a root
+    pub fn set_parent(node: &Rc<RefCell<TransformNode>>, parent: Option<&Rc<RefCell<TransformNode>>>) {
+        if let Some(parent) = parent {
+            let mut ancestor = Some(parent.clone());
+            while let Some(a) = ancestor {
+                if Rc::ptr_eq(&a, node) {
+                    panic!("TransformNode::set_parent: You tried to create a cycle in the hierarchy")
+                }
+                ancestor = a.borrow().parent();
+            }
+        }
+
+        node.borrow_mut().parent = parent.map(Rc::downgrade);
+    }
+
+    // parent's world transform * local, nodes whose parent was dropped act as roots
+    pub fn world_transform(&self) -> Matrix4x4 {
+        match self.parent() {
+            Some(parent) => parent.borrow().world_transform() * self.local.clone(),
+            None => self.local.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::f64::consts::PI;
+    use std::rc::Rc;
+
+    use macroquad::prelude::WHITE;
+
+    use crate::math::frustum::Frustum;
+    use crate::math::matrix4x4::Matrix4x4;
+    use crate::math::plane::Plane;
+    use crate::math::vec_3d::Vec3d;
+    use crate::object::{Cube, Mesh, Object, ObjectNameTag};
+    use crate::triangle::Triangle;
+
+    use super::{Scene, SceneStats, SpatialGrid, TransformNode};
+
+    // box [lo, hi]^3 with inward facing planes
+    fn box_frustum(lo: f64, hi: f64) -> Frustum {
+        let mut planes = vec![];
+        for axis in [Vec3d::new(1.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 0.0, 1.0)] {
+            planes.push(Plane::new(axis, axis * lo));
+            planes.push(Plane::new(-&axis, axis * hi));
+        }
+        Frustum::new(planes)
+    }
+
+    #[test]
+    fn query_single_cell() {
+        let mut grid = SpatialGrid::new(10.0);
+        let a = ObjectNameTag::new("a");
+        let b = ObjectNameTag::new("b");
+        let c = ObjectNameTag::new("c");
+        let d = ObjectNameTag::new("d");
+        grid.insert(&a, &Vec3d::new(1.0, 1.0, 1.0));
+        grid.insert(&b, &Vec3d::new(9.0, 2.0, 5.0));
+        grid.insert(&c, &Vec3d::new(15.0, 1.0, 1.0));
+        grid.insert(&d, &Vec3d::new(-5.0, 50.0, 1.0));
+
+        let frustum = box_frustum(2.0, 8.0);
+        let mut found = grid.query_frustum(&frustum);
+        found.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        assert_eq!(found, vec![a.clone(), b.clone()]);
+
+        // moving an object updates its cell
+        grid.insert(&c, &Vec3d::new(5.0, 5.0, 5.0));
+        grid.insert(&a, &Vec3d::new(25.0, 5.0, 5.0));
+        let mut found = grid.query_frustum(&frustum);
+        found.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        assert_eq!(found, vec![b, c]);
+        assert_eq!(grid.len(), 4);
+    }
+
+    #[test]
+    fn lazy_world_transform() {
+        let root_local = Matrix4x4::translation(&Vec3d::new(1.0, 0.0, 0.0));
+        let child_local = Matrix4x4::rotation(&Vec3d::new(0.0, 0.5, 0.0));
+        let grandchild_local = Matrix4x4::translation(&Vec3d::new(0.0, 0.0, 2.0));
+
+        let root = TransformNode::new(ObjectNameTag::new("root"), root_local.clone());
+        let child = TransformNode::new(ObjectNameTag::new("child"), child_local.clone());
+        let grandchild = TransformNode::new(ObjectNameTag::new("grandchild"), grandchild_local.clone());
+        TransformNode::set_parent(&child, Some(&root));
+        TransformNode::set_parent(&grandchild, Some(&child));
+
+        let shift = Matrix4x4::translation(&Vec3d::new(0.0, 3.0, 0.0));
+        *root.borrow_mut().local_mut() = shift.clone() * root_local.clone();
+
+        let moved_root = shift * root_local;
+        assert_eq!(child.borrow().world_transform(), moved_root.clone() * child_local.clone());
+        assert_eq!(
+            grandchild.borrow().world_transform(),
+            moved_root * child_local.clone() * grandchild_local.clone()
+        );
+
+        // descendants' stored transforms are untouched
+        assert_eq!(*child.borrow().local(), child_local);
+        assert_eq!(*grandchild.borrow().local(), grandchild_local);
+    }
+
+    #[test]
+    #[should_panic]
+    fn transform_node_cycle() {
+        let a = TransformNode::new(ObjectNameTag::new("a"), Matrix4x4::identity());
+        let b = TransformNode::new(ObjectNameTag::new("b"), Matrix4x4::identity());
+        TransformNode::set_parent(&b, Some(&a));
+        TransformNode::set_parent(&a, Some(&b));
+    }
+
+    #[test]
+    fn rotate_group() {
+        let a = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("a"), 1.0, WHITE)));
+        let b = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("b"), 1.0, WHITE)));
+        let c = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("c"), 1.0, WHITE)));
+        a.borrow_mut().translate(&Vec3d::new(3.0, 0.0, 0.0));
+        b.borrow_mut().translate(&Vec3d::new(1.0, 0.0, 2.0));
+        c.borrow_mut().translate(&Vec3d::new(3.0, 0.0, 0.0));
+
+        let mut scene = Scene::new();
+        scene.add(a.clone());
+        scene.add(b.clone());
+        scene.add(c.clone());
+
+        // a quarter turn about the y axis through (1, 0, 0)
+        let pivot = Vec3d::new(1.0, 0.0, 0.0);
+        let tags = [ObjectNameTag::new("a"), ObjectNameTag::new("b")];
+        scene.rotate_group(&tags, &pivot, &Vec3d::new(0.0, PI / 2.0, 0.0));
+
+        assert_eq!(*a.borrow().position(), Vec3d::new(1.0, 0.0, -2.0));
+        assert_eq!(*b.borrow().position(), Vec3d::new(3.0, 0.0, 0.0));
+        assert_eq!(*c.borrow().position(), Vec3d::new(3.0, 0.0, 0.0));
+
+        // the cubes turned along with their orbit
+        let turn = Matrix4x4::rotation(&Vec3d::new(0.0, PI / 2.0, 0.0));
+        assert_eq!(*a.borrow().transform_matrix(), turn);
+        assert_eq!(*c.borrow().transform_matrix(), Matrix4x4::identity());
+    }
+
+    #[test]
+    fn rotate_group_keeps_own_rotation() {
+        let a = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("a"), 1.0, WHITE)));
+        a.borrow_mut().rotate(&Vec3d::new(PI / 2.0, 0.0, 0.0));
+        a.borrow_mut().translate(&Vec3d::new(3.0, 0.0, 0.0));
+
+        let mut scene = Scene::new();
+        scene.add(a.clone());
+        scene.rotate_group(&[ObjectNameTag::new("a")], &Vec3d::new(1.0, 0.0, 0.0), &Vec3d::new(0.0, PI / 2.0, 0.0));
+
+        // the group turn is applied on top of the tilt the cube already had
+        let tilt = Matrix4x4::rotation(&Vec3d::new(PI / 2.0, 0.0, 0.0));
+        let turn = Matrix4x4::rotation(&Vec3d::new(0.0, PI / 2.0, 0.0));
+        assert_eq!(*a.borrow().position(), Vec3d::new(1.0, 0.0, -2.0));
+        assert_eq!(*a.borrow().transform_matrix(), turn * tilt);
+    }
+
+    #[test]
+    fn subtree_visibility() {
+        let cube = |name: &str| Rc::new(RefCell::new(Cube::new(ObjectNameTag::new(name), 1.0, WHITE)));
+        let (root, parent, child, grandchild) = (cube("Root"), cube("Parent"), cube("Child"), cube("Grandchild"));
+        child.borrow_mut().attach(grandchild.clone());
+        parent.borrow_mut().attach(child.clone());
+        root.borrow_mut().attach(parent.clone());
+
+        let mut scene = Scene::new();
+        scene.add(root.clone());
+
+        assert!(scene.set_subtree_visible(&ObjectNameTag::new("Parent"), false));
+        assert!(root.borrow().visible());
+        for mesh in [&parent, &child, &grandchild] {
+            assert!(!mesh.borrow().visible());
+        }
+
+        assert!(scene.set_subtree_visible(&ObjectNameTag::new("Root"), true));
+        assert!([&root, &parent, &child, &grandchild].iter().all(|m| m.borrow().visible()));
+        assert!(!scene.set_subtree_visible(&ObjectNameTag::new("Missing"), false));
+    }
+
+    #[test]
+    fn stats() {
+        let mut scene = Scene::new();
+        assert_eq!(scene.stats(), SceneStats::default());
+
+        let parent = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Parent"), 1.0, WHITE)));
+        let child = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Child"), 1.0, WHITE)));
+        parent.borrow_mut().attach(child.clone());
+        scene.add(parent);
+
+        let stats = scene.stats();
+        assert_eq!(stats.meshes, 2);
+        assert_eq!(stats.triangles, 24);
+        assert_eq!(stats.vertices, 72);
+        assert!(stats.bytes >= 24 * std::mem::size_of::<Triangle>());
+    }
+
+    #[test]
+    fn all_objects_by_identity() {
+        let first = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE)));
+        let second = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Cube"), 1.0, WHITE)));
+        let shared = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Shared"), 1.0, WHITE)));
+        first.borrow_mut().attach(shared.clone());
+        second.borrow_mut().attach(shared.clone());
+
+        let mut scene = Scene::new();
+        scene.add(first.clone());
+        scene.add(second.clone());
+
+        // both cubes are kept despite the same name, the child attached to both comes once
+        let all = scene.all_objects();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all.iter().filter(|o| o.borrow().nametag() == &ObjectNameTag::new("Cube")).count(), 2);
+    }
+
+    #[test]
+    fn all_world_triangles() {
+        let parent = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Parent"), 1.0, WHITE)));
+        let child = Rc::new(RefCell::new(Cube::new(ObjectNameTag::new("Child"), 1.0, WHITE)));
+        child.borrow_mut().translate(&Vec3d::new(3.0, 0.0, 0.0));
+        parent.borrow_mut().attach(child.clone());
+        parent.borrow_mut().translate(&Vec3d::new(0.0, 0.0, 5.0));
+
+        let mut scene = Scene::new();
+        scene.add(parent);
+
+        let triangles = scene.all_world_triangles();
+        assert_eq!(triangles.len(), 24);
+
+        // unit cubes centered at (0, 0, 5) and (3, 0, 5)
+        let in_cube = |t: &&Triangle, x: f64| {
+            let p = t.position();
+            (p.x() - x).abs() <= 0.5 && p.y().abs() <= 0.5 && (p.z() - 5.0).abs() <= 0.5
+        };
+        assert_eq!(triangles.iter().filter(|t| in_cube(t, 0.0)).count(), 12);
+        assert_eq!(triangles.iter().filter(|t| in_cube(t, 3.0)).count(), 12);
+    }
+}
@@ -1,9 +1,11 @@
-use std::f64::EPSILON;
 use std::ops;
+use std::sync::Arc;
 
 use macroquad::prelude::Color;
 
+use crate::material::{lerp_color, Texture};
 use crate::math::matrix4x4::Matrix4x4;
+use crate::math::vec_2d::Vec2d;
 use crate::math::vec_3d::Vec3d;
 use crate::math::vec_4d::Vec4d;
 
@@ -12,6 +14,14 @@ pub struct Triangle {
     color: Color,
     points: [Vec4d; 3],
     normal: Vec3d,
+    vertex_normals: Option<[Vec3d; 3]>,
+    vertex_colors: Option<[Color; 3]>,
+    material_id: Option<u32>,
+    uvs: Option<[Vec2d; 3]>,
+    texture: Option<Arc<Texture>>,
+    atlas_rect: Option<(Vec2d, Vec2d)>,
+    render_priority: i32,
+    group: u32,
 }
 
 impl Triangle {
@@ -26,33 +36,217 @@ impl Triangle {
             color,
             points: arr,
             normal: norm,
+            vertex_normals: None,
+            vertex_colors: None,
+            material_id: None,
+            uvs: None,
+            texture: None,
+            atlas_rect: None,
+            render_priority: 0,
+            group: 0,
         }
     }
+
+    // Smooth shaded triangle, one color per vertex. Its flat color() is their average.
+    pub fn new_shaded(colors: [Color; 3], p1: Vec4d, p2: Vec4d, p3: Vec4d) -> Self {
+        let mut res = Self::new(colors[0], p1, p2, p3);
+        res.set_shaded(colors);
+        res
+    }
+
+    // one color per vertex, the flat color becomes their average like with new_shaded
+    pub fn set_shaded(&mut self, colors: [Color; 3]) {
+        let average = |channel: fn(&Color) -> f32| colors.iter().map(channel).sum::<f32>() / 3.0;
+        self.color = Color::new(average(|c| c.r), average(|c| c.g), average(|c| c.b), average(|c| c.a));
+        self.vertex_colors = Some(colors);
+    }
+
+    // new triangle with the same attributes (color, material, ...) but different points
+    pub fn with_points(&self, p1: Vec4d, p2: Vec4d, p3: Vec4d) -> Self {
+        let arr = [p1, p2, p3];
+        let norm = Self::calculate_normal(&arr);
+        Self {
+            points: arr,
+            normal: norm,
+            ..self.clone()
+        }
+    }
+
+    // New triangle whose vertices lie on the edges of this one: vertex (a, b, t) is the point
+    // between vertices a and b at t. Per-vertex attributes are interpolated the same way.
+    // The result lies in the same plane, so it keeps this triangle's normal instead of recomputing it
+    // (which would drift for slivers and flip with the vertex order).
+    pub fn lerp_vertices(&self, vertices: [(usize, usize, f64); 3]) -> Self {
+        let point = |(a, b, t): (usize, usize, f64)| self.points[a] + ((self.points[b] - self.points[a]) * t);
+        let mut res = Self {
+            points: [point(vertices[0]), point(vertices[1]), point(vertices[2])],
+            ..self.clone()
+        };
+
+        if let Some(normals) = &self.vertex_normals {
+            let normal = |(a, b, t): (usize, usize, f64)| (normals[a] + ((normals[b] - normals[a]) * t)).normalized();
+            res.vertex_normals = Some([normal(vertices[0]), normal(vertices[1]), normal(vertices[2])]);
+        }
+
+        if let Some(colors) = &self.vertex_colors {
+            let color = |(a, b, t): (usize, usize, f64)| lerp_color(colors[a], colors[b], t as f32);
+            res.vertex_colors = Some([color(vertices[0]), color(vertices[1]), color(vertices[2])]);
+        }
+
+        if let Some(uvs) = &self.uvs {
+            let uv = |(a, b, t): (usize, usize, f64)| {
+                Vec2d::new(uvs[a].x() + (uvs[b].x() - uvs[a].x()) * t, uvs[a].y() + (uvs[b].y() - uvs[a].y()) * t)
+            };
+            res.uvs = Some([uv(vertices[0]), uv(vertices[1]), uv(vertices[2])]);
+        }
+
+        res
+    }
+
+    // same triangle wound the other way (vertices 1 and 2 swapped with their attributes), normal reversed
+    pub fn flipped(&self) -> Self {
+        let mut res = self.lerp_vertices([(0, 0, 0.0), (2, 2, 0.0), (1, 1, 0.0)]);
+        res.normal = -&self.normal;
+        res
+    }
+
     pub fn calculate_normal(points: &[Vec4d; 3]) -> Vec3d {
-        let v1 = Vec3d::from_vec4d(&(&points[1] - &points[0]));
-        let v2 = Vec3d::from_vec4d(&(&points[2] - &points[0]));
+        let v1 = Vec3d::from_vec4d(&(points[1] - points[0]));
+        let v2 = Vec3d::from_vec4d(&(points[2] - points[0]));
         let cross = v1.cross(&v2);
-        if cross.sqr_abs() > EPSILON {
+        if cross.sqr_abs() > f64::EPSILON {
             cross.normalized()
         } else {
             Vec3d::new(0.0, 0.0, 0.0)
         }
     }
 
+    pub fn area(&self) -> f64 {
+        let v1 = Vec3d::from_vec4d(&(self.points[1] - self.points[0]));
+        let v2 = Vec3d::from_vec4d(&(self.points[2] - self.points[0]));
+        0.5 * v1.cross(&v2).abs()
+    }
+
+    pub fn perimeter(&self) -> f64 {
+        (0..3).map(|i| Vec3d::from_vec4d(&(self.points[(i + 1) % 3] - self.points[i])).abs()).sum()
+    }
+
+    // collapsed to a line or a point, such triangles cover no pixels and have no meaningful normal
+    pub fn is_degenerate(&self) -> bool {
+        self.area() < f64::EPSILON
+    }
+
     pub fn normal(&self) -> Vec3d {
-        self.normal.clone()
+        self.normal
+    }
+
+    // the normal as it would be after transforming the triangle by m (uses m's normal matrix)
+    pub fn transform_normal(&self, m: &Matrix4x4) -> Vec3d {
+        (&m.normal_matrix() * &self.normal).normalized()
+    }
+
+    // smooth shading normals, one per vertex
+    pub fn vertex_normals(&self) -> Option<&[Vec3d; 3]> {
+        self.vertex_normals.as_ref()
+    }
+    pub fn vertex_normals_mut(&mut self) -> &mut Option<[Vec3d; 3]> {
+        &mut self.vertex_normals
+    }
+
+    // colors of the vertices, color() stays the flat color of the whole triangle
+    pub fn vertex_colors(&self) -> Option<&[Color; 3]> {
+        self.vertex_colors.as_ref()
+    }
+    pub fn vertex_colors_mut(&mut self) -> &mut Option<[Color; 3]> {
+        &mut self.vertex_colors
     }
 
-    fn is_point_inside(&self, point: &Vec3d) -> bool {
+    // Vertex colors blended by the barycentric weights (u, v, w) of the points,
+    // the flat color for triangles without vertex colors.
+    pub fn color_at_bary(&self, u: f64, v: f64, w: f64) -> Color {
+        let Some(colors) = &self.vertex_colors else {
+            return self.color;
+        };
+        let (u, v, w) = (u as f32, v as f32, w as f32);
+        let blend =
+            |channel: fn(&Color) -> f32| u * channel(&colors[0]) + v * channel(&colors[1]) + w * channel(&colors[2]);
+        Color::new(blend(|c| c.r), blend(|c| c.g), blend(|c| c.b), blend(|c| c.a))
+    }
+
+    // texture coordinates, one per vertex
+    pub fn uvs(&self) -> Option<&[Vec2d; 3]> {
+        self.uvs.as_ref()
+    }
+    pub fn uvs_mut(&mut self) -> &mut Option<[Vec2d; 3]> {
+        &mut self.uvs
+    }
+
+    pub fn texture(&self) -> Option<&Arc<Texture>> {
+        self.texture.as_ref()
+    }
+    pub fn texture_mut(&mut self) -> &mut Option<Arc<Texture>> {
+        &mut self.texture
+    }
+
+    // Restricts sampling to the sub-rect of the texture at offset with the given size
+    // (in uv units), so uvs in [0, 1] address one image of a sprite sheet.
+    pub fn set_atlas_rect(&mut self, offset: Vec2d, size: Vec2d) {
+        self.atlas_rect = Some((offset, size));
+    }
+    pub fn atlas_rect(&self) -> Option<&(Vec2d, Vec2d)> {
+        self.atlas_rect.as_ref()
+    }
+
+    // Tangent space basis for normal mapping: unit tangent along +u and bitangent along +v,
+    // the tangent made orthogonal to the normal and the bitangent = normal x tangent
+    // (negated for mirrored uvs). Panics when the triangle has no uvs.
+    pub fn tangent_bitangent(&self) -> (Vec3d, Vec3d) {
+        let uvs = self.uvs.as_ref().expect("Triangle::tangent_bitangent: triangle has no uvs");
+        let e1 = Vec3d::from_vec4d(&(self.points[1] - self.points[0]));
+        let e2 = Vec3d::from_vec4d(&(self.points[2] - self.points[0]));
+        let (du1, dv1) = (uvs[1].x() - uvs[0].x(), uvs[1].y() - uvs[0].y());
+        let (du2, dv2) = (uvs[2].x() - uvs[0].x(), uvs[2].y() - uvs[0].y());
+
+        let det = du1 * dv2 - du2 * dv1;
+        let (tangent, bitangent) = if det.abs() > f64::EPSILON {
+            ((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det)
+        } else {
+            // degenerate uvs, any basis in the triangle's plane will do
+            (e1, self.normal.cross(&e1))
+        };
+
+        let tangent = (tangent - (self.normal * self.normal.dot(&tangent))).normalized();
+        let mut res_bitangent = self.normal.cross(&tangent);
+        if res_bitangent.dot(&bitangent) < 0.0 {
+            res_bitangent = -res_bitangent;
+        }
+
+        (tangent, res_bitangent)
+    }
+
+    // texel at the triangle's uv coordinates, mapped into the atlas rect if there is one
+    pub fn sample_texture(&self, u: f64, v: f64) -> Option<Color> {
+        let texture = self.texture.as_ref()?;
+        match &self.atlas_rect {
+            Some((offset, size)) => {
+                // stay just inside the rect so its far border doesn't bleed into the next image
+                let (u, v) = (u.clamp(0.0, 1.0 - 1e-9), v.clamp(0.0, 1.0 - 1e-9));
+                Some(texture.sample(offset.x() + u * size.x(), offset.y() + v * size.y()))
+            }
+            None => Some(texture.sample(u, v)),
+        }
+    }
+
+    pub fn is_point_inside(&self, point: &Vec3d) -> bool {
         let tri_normal = self.normal();
-        let dot1 = (point.clone() - Vec3d::from_vec4d(&self.points[0]))
-            .cross(&Vec3d::from_vec4d(&(&self.points[1] - &self.points[0])))
+        let dot1 = (*point - Vec3d::from_vec4d(&self.points[0]))
+            .cross(&Vec3d::from_vec4d(&(self.points[1] - self.points[0])))
             .dot(&tri_normal);
-        let dot2 = (point.clone() - Vec3d::from_vec4d(&self.points[1]))
-            .cross(&Vec3d::from_vec4d(&(&self.points[2] - &self.points[1])))
+        let dot2 = (*point - Vec3d::from_vec4d(&self.points[1]))
+            .cross(&Vec3d::from_vec4d(&(self.points[2] - self.points[1])))
             .dot(&tri_normal);
-        let dot3 = (point.clone() - Vec3d::from_vec4d(&self.points[2]))
-            .cross(&Vec3d::from_vec4d(&(&self.points[0] - &self.points[2])))
+        let dot3 = (*point - Vec3d::from_vec4d(&self.points[2]))
+            .cross(&Vec3d::from_vec4d(&(self.points[0] - self.points[2])))
             .dot(&tri_normal);
 
         if (dot1 >= 0.0 && dot2 >= 0.0 && dot3 >= 0.0) || (dot1 <= 0.0 && dot2 <= 0.0 && dot3 <= 0.0) {
@@ -61,8 +255,103 @@ impl Triangle {
         false
     }
 
+    // distance along dir (in units of dir) to where the ray hits the triangle, None on a miss
+    pub fn ray_intersect(&self, origin: &Vec3d, dir: &Vec3d) -> Option<f64> {
+        self.ray_intersect_eps(origin, dir, 0.0)
+    }
+
+    // Same as ray_intersect, but hits up to eps (in barycentric units) outside the edges count,
+    // so rays through a shared edge aren't lost to rounding between the two triangles.
+    pub fn ray_intersect_eps(&self, origin: &Vec3d, dir: &Vec3d, eps: f64) -> Option<f64> {
+        let a = Vec3d::from_vec4d(&self.points[0]);
+        let e1 = Vec3d::from_vec4d(&self.points[1]) - a;
+        let e2 = Vec3d::from_vec4d(&self.points[2]) - a;
+
+        let p = dir.cross(&e2);
+        let det = e1.dot(&p);
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let s = origin - &a;
+        let u = s.dot(&p) / det;
+        let q = s.cross(&e1);
+        let v = dir.dot(&q) / det;
+        if u < -eps || v < -eps || u + v > 1.0 + eps {
+            return None;
+        }
+
+        let t = e2.dot(&q) / det;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
     pub fn distance(&self, point: &Vec3d) -> f64 {
-        self.normal().dot(&(Vec3d::from_vec4d(&self.points[0]) - point.clone()))
+        self.normal().dot(&(Vec3d::from_vec4d(&self.points[0]) - *point))
+    }
+
+    // nearest point of the triangle (including its edges and vertices) to the given point
+    pub fn closest_point(&self, point: &Vec3d) -> Vec3d {
+        let a = Vec3d::from_vec4d(&self.points[0]);
+        let b = Vec3d::from_vec4d(&self.points[1]);
+        let c = Vec3d::from_vec4d(&self.points[2]);
+        let ab = b - a;
+        let ac = c - a;
+
+        let ap = point - &a;
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return a;
+        }
+
+        let bp = point - &b;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return b;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return a + (ab * (d1 / (d1 - d3)));
+        }
+
+        let cp = point - &c;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return c;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return a + (ac * (d2 / (d2 - d6)));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+            return b + ((c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6))));
+        }
+
+        let denom = 1.0 / (va + vb + vc);
+        (a + (ab * (vb * denom))) + (ac * (vc * denom))
+    }
+
+    // Signed solid angle the triangle subtends as seen from the point, positive when
+    // the point is on the back side (behind the normal).
+    pub fn solid_angle(&self, point: &Vec3d) -> f64 {
+        let a = Vec3d::from_vec4d(&self.points[0]) - *point;
+        let b = Vec3d::from_vec4d(&self.points[1]) - *point;
+        let c = Vec3d::from_vec4d(&self.points[2]) - *point;
+        let (la, lb, lc) = (a.abs(), b.abs(), c.abs());
+
+        let numerator = a.dot(&b.cross(&c));
+        let denominator = la * lb * lc + a.dot(&b) * lc + a.dot(&c) * lb + b.dot(&c) * la;
+        2.0 * numerator.atan2(denominator)
     }
 
     pub fn color(&self) -> Color {
@@ -72,20 +361,310 @@ impl Triangle {
         &mut self.color
     }
 
+    pub fn material_id(&self) -> Option<u32> {
+        self.material_id
+    }
+    pub fn material_id_mut(&mut self) -> &mut Option<u32> {
+        &mut self.material_id
+    }
+
+    // draw order before depth: higher priorities are drawn after lower ones, 0 by default
+    pub fn render_priority(&self) -> i32 {
+        self.render_priority
+    }
+    pub fn render_priority_mut(&mut self) -> &mut i32 {
+        &mut self.render_priority
+    }
+
+    // layer within its mesh, see Mesh::group_order; 0 by default
+    pub fn group(&self) -> u32 {
+        self.group
+    }
+    pub fn group_mut(&mut self) -> &mut u32 {
+        &mut self.group
+    }
+
     pub fn position(&self) -> Vec3d {
-        Vec3d::from_vec4d(&((&self.points[0] + &self.points[1] + &self.points[2]) / 3.0))
+        Vec3d::from_vec4d(&((self.points[0] + self.points[1] + self.points[2]) / 3.0))
+    }
+
+    // The three edges p0-p1, p1-p2, p2-p0 as x, y line segments, for drawing projected triangles
+    // (whose points are in screen space) as wireframe.
+    pub fn edges(&self) -> [(Vec2d, Vec2d); 3] {
+        let p = |i: usize| Vec2d::from_vec4d(&self.points[i % 3]);
+        [(p(0), p(1)), (p(1), p(2)), (p(2), p(3))]
+    }
+}
+
+// A model matrix with what transform_by needs besides it, worked out once for all the triangles of a mesh:
+// whether it mirrors (the winding is swapped to keep normals outward) and its normal matrix.
+pub struct TriangleTransform {
+    matrix: Matrix4x4,
+    normal_matrix: Matrix4x4,
+    mirroring: bool,
+}
+
+impl TriangleTransform {
+    pub fn new(matrix: &Matrix4x4) -> Self {
+        Self {
+            matrix: matrix.clone(),
+            normal_matrix: matrix.normal_matrix(),
+            mirroring: matrix.is_mirroring(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4x4 {
+        &self.matrix
+    }
+}
+
+impl Triangle {
+    // Moves the triangle by a model matrix: vertex normals go through the normal matrix and under
+    // a mirroring matrix two vertices are swapped, so the face stays facing outward.
+    pub fn transform_by(&self, transform: &TriangleTransform) -> Triangle {
+        let [a, b, c] = if transform.mirroring { [0, 2, 1] } else { [0, 1, 2] };
+        let m = &transform.matrix;
+
+        let mut res = self.with_points(m * &self.points[a], m * &self.points[b], m * &self.points[c]);
+
+        if let Some(normals) = &self.vertex_normals {
+            let n = &transform.normal_matrix;
+            res.vertex_normals = Some([
+                (n * &normals[a]).normalized(),
+                (n * &normals[b]).normalized(),
+                (n * &normals[c]).normalized(),
+            ]);
+        }
+
+        if let Some(colors) = &self.vertex_colors {
+            res.vertex_colors = Some([colors[a], colors[b], colors[c]]);
+        }
+
+        if let Some(uvs) = &self.uvs {
+            res.uvs = Some([uvs[a].clone(), uvs[b].clone(), uvs[c].clone()]);
+        }
+
+        res
     }
 }
 
 impl ops::Mul<&Matrix4x4> for &Triangle {
     type Output = Triangle;
 
+    // plain transform of the points (and vertex normals as directions), see transform_by for model matrices
     fn mul(self, rhs: &Matrix4x4) -> Self::Output {
-        Triangle::new(
-            self.color,
-            rhs.clone() * self.points[0].clone(),
-            rhs.clone() * self.points[1].clone(),
-            rhs.clone() * self.points[2].clone(),
-        )
+        let mut res = self.with_points(rhs * &self.points[0], rhs * &self.points[1], rhs * &self.points[2]);
+
+        if let Some(normals) = &self.vertex_normals {
+            res.vertex_normals = Some([
+                (rhs * &normals[0]).normalized(),
+                (rhs * &normals[1]).normalized(),
+                (rhs * &normals[2]).normalized(),
+            ]);
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use macroquad::prelude::{Color, WHITE};
+
+    use crate::material::Texture;
+    use crate::math::matrix4x4::Matrix4x4;
+    use crate::math::vec_2d::Vec2d;
+    use crate::math::vec_3d::Vec3d;
+    use crate::math::vec_4d::Vec4d;
+
+    use super::{Triangle, TriangleTransform};
+
+    #[test]
+    fn vertex_color_blending() {
+        let (red, green, blue) = (
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            Color::new(0.0, 1.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 1.0, 0.4),
+        );
+        let tri = Triangle::new_shaded(
+            [red, green, blue],
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(1.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 1.0, 0.0, 1.0),
+        );
+
+        let third = 1.0 / 3.0;
+        assert_eq!(tri.color(), Color::new(third as f32, third as f32, third as f32, 0.8));
+        assert_eq!(tri.color_at_bary(1.0, 0.0, 0.0), red);
+        assert_eq!(tri.color_at_bary(0.0, 0.0, 1.0), blue);
+        assert_eq!(tri.color_at_bary(0.5, 0.5, 0.0), Color::new(0.5, 0.5, 0.0, 1.0));
+        assert_eq!(tri.color_at_bary(third, third, third), tri.color());
+
+        let flat = Triangle::new(red, tri.points()[0], tri.points()[1], tri.points()[2]);
+        assert_eq!(flat.color_at_bary(0.2, 0.3, 0.5), red);
+    }
+
+    #[test]
+    fn edges() {
+        let tri = Triangle::new(
+            WHITE,
+            Vec4d::new(1.0, 2.0, 0.5, 1.0),
+            Vec4d::new(3.0, 4.0, 0.5, 1.0),
+            Vec4d::new(5.0, 0.0, 0.5, 1.0),
+        );
+        let edges = tri.edges();
+        assert_eq!(edges[0], (Vec2d::new(1.0, 2.0), Vec2d::new(3.0, 4.0)));
+        assert_eq!(edges[1], (Vec2d::new(3.0, 4.0), Vec2d::new(5.0, 0.0)));
+        assert_eq!(edges[2], (Vec2d::new(5.0, 0.0), Vec2d::new(1.0, 2.0)));
+    }
+
+    #[test]
+    fn area_and_perimeter() {
+        let tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 1.0, 1.0),
+            Vec4d::new(3.0, 0.0, 1.0, 1.0),
+            Vec4d::new(0.0, 4.0, 1.0, 1.0),
+        );
+        assert!((tri.area() - 6.0).abs() < 1e-12);
+        assert!((tri.perimeter() - 12.0).abs() < 1e-12);
+        assert!(!tri.is_degenerate());
+
+        let p = Vec4d::new(1.0, 2.0, 3.0, 1.0);
+        let line = Triangle::new(WHITE, p, Vec4d::new(2.0, 3.0, 4.0, 1.0), Vec4d::new(3.0, 4.0, 5.0, 1.0));
+        assert!(line.is_degenerate() && Triangle::new(WHITE, p, p, p).is_degenerate());
+    }
+
+    #[test]
+    fn normals_under_non_uniform_scale() {
+        let mut tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(1.0, -1.0, 0.0, 1.0),
+            Vec4d::new(0.0, 0.0, 1.0, 1.0),
+        );
+        *tri.vertex_normals_mut() = Some([tri.normal(), tri.normal(), tri.normal()]);
+        let m = Matrix4x4::translation(&Vec3d::new(3.0, 0.0, 0.0)) * Matrix4x4::scale(&Vec3d::new(4.0, 1.0, 0.5));
+
+        let transformed = tri.transform_by(&TriangleTransform::new(&m));
+        assert_eq!(tri.transform_normal(&m), transformed.normal());
+        for n in transformed.vertex_normals().unwrap() {
+            assert_eq!(*n, transformed.normal());
+        }
+    }
+
+    #[test]
+    fn transform_by_keeps_mirrored_faces_outward() {
+        let tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(1.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 1.0, 0.0, 1.0),
+        );
+        let mirror = Matrix4x4::scale(&Vec3d::new(-1.0, 1.0, 1.0));
+
+        // the plain product keeps the vertex order, so the normal flips with the mirror
+        assert_eq!((&tri * &mirror).normal(), -tri.normal());
+        let mirrored = tri.transform_by(&TriangleTransform::new(&mirror));
+        assert_eq!(mirrored.normal(), tri.normal());
+        assert_eq!(mirrored.points()[1], Vec4d::new(0.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn transform_by_reference() {
+        let tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 1.0, 2.0, 1.0),
+            Vec4d::new(3.0, 1.0, 0.0, 1.0),
+        );
+        let m = Matrix4x4::translation(&Vec3d::new(1.0, -2.0, 3.0))
+            * Matrix4x4::rotation(&Vec3d::new(0.4, 1.1, -0.3))
+            * Matrix4x4::scale(&Vec3d::new(2.0, 1.0, 0.5));
+
+        let transformed = &tri * &m;
+        for (p, original) in transformed.points().iter().zip(tri.points()) {
+            assert_eq!(*p, m.clone() * *original);
+        }
+        assert_eq!(transformed.normal(), Triangle::calculate_normal(transformed.points()));
+    }
+
+    #[test]
+    fn atlas_rect_samples_sub_image() {
+        let colors = [
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            Color::new(0.0, 1.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 1.0, 1.0),
+            Color::new(1.0, 1.0, 0.0, 1.0),
+        ];
+        // 4x4 texture made of four 2x2 quadrants
+        let texels = (0..16).map(|i| colors[(i / 8) * 2 + (i % 4) / 2]).collect();
+
+        let mut tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(1.0, 0.0, 0.0, 1.0),
+            Vec4d::new(0.0, 1.0, 0.0, 1.0),
+        );
+        *tri.texture_mut() = Some(Arc::new(Texture::new(4, 4, texels)));
+        assert_eq!(tri.sample_texture(0.9, 0.9), Some(colors[3]));
+
+        tri.set_atlas_rect(Vec2d::new(0.0, 0.0), Vec2d::new(0.5, 0.5));
+        for i in 0..=10 {
+            for j in 0..=10 {
+                assert_eq!(tri.sample_texture(i as f64 / 10.0, j as f64 / 10.0), Some(colors[0]));
+            }
+        }
+    }
+
+    #[test]
+    fn tangent_follows_u() {
+        // u runs along the (1, 1, 0) diagonal, v along (-1, 1, 0)
+        let mut tri = Triangle::new(
+            WHITE,
+            Vec4d::new(0.0, 0.0, 0.0, 1.0),
+            Vec4d::new(1.0, 1.0, 0.0, 1.0),
+            Vec4d::new(-1.0, 1.0, 0.0, 1.0),
+        );
+        *tri.uvs_mut() = Some([Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 1.0)]);
+
+        let (tangent, bitangent) = tri.tangent_bitangent();
+        let diagonal = Vec3d::new(1.0, 1.0, 0.0).normalized();
+        assert_eq!(tangent, diagonal);
+        assert!(tangent.dot(&tri.normal()).abs() < 1e-12);
+        assert!(bitangent.dot(&tangent).abs() < 1e-12);
+        assert_eq!(bitangent, Vec3d::new(-1.0, 1.0, 0.0).normalized());
+
+        // skewed uvs: the raw tangent leaves the plane's u axis, the result is still orthonormal
+        *tri.uvs_mut() = Some([Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.3), Vec2d::new(0.2, 1.0)]);
+        let (tangent, bitangent) = tri.tangent_bitangent();
+        assert!(tangent.dot(&tri.normal()).abs() < 1e-12);
+        assert!((tangent.abs() - 1.0).abs() < 1e-12 && (bitangent.abs() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ray_hits_shared_edge() {
+        // two triangles of a quad sharing the (0, 0) - (1, 1) diagonal
+        let a = Vec4d::new(0.0, 0.0, 0.0, 1.0);
+        let c = Vec4d::new(1.0, 1.0, 0.0, 1.0);
+        let tris = [
+            Triangle::new(WHITE, a, Vec4d::new(1.0, 0.0, 0.0, 1.0), c),
+            Triangle::new(WHITE, a, c, Vec4d::new(0.0, 1.0, 0.0, 1.0)),
+        ];
+
+        let dir = Vec3d::new(0.0, 0.0, 1.0);
+        for i in 1..10 {
+            let k = i as f64 * 0.1;
+            let origin = Vec3d::new(k, k, -2.0);
+            let hits: Vec<f64> = tris.iter().filter_map(|t| t.ray_intersect_eps(&origin, &dir, 1e-9)).collect();
+            assert!(!hits.is_empty());
+            assert!(hits.iter().all(|&t| (t - 2.0).abs() < 1e-9));
+        }
+
+        assert!(tris[0].ray_intersect(&Vec3d::new(0.9, 0.1, -2.0), &dir).is_some());
+        assert!(tris[0].ray_intersect(&Vec3d::new(0.9, 0.1, 2.0), &dir).is_none());
+        assert!(tris[0].ray_intersect_eps(&Vec3d::new(1.1, 0.1, -2.0), &dir, 1e-9).is_none());
     }
 }
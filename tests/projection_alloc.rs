@@ -0,0 +1,56 @@
+// The counting allocator replaces the global allocator of the whole test binary, so this check
+// lives in its own integration test instead of the camera unit tests.
+
+// the parallel projection allocates per call by design
+#![cfg(not(feature = "parallel"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use macroquad::prelude::WHITE;
+
+use rust_3d_course::camera::MyCamera;
+use rust_3d_course::math::vec_3d::Vec3d;
+use rust_3d_course::object::{Cube, Object, ObjectNameTag};
+
+// counts heap allocations made by the current thread, so parallel tests don't interfere
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+fn steady_state_projection_does_not_allocate() {
+    // cut by the near and left planes
+    let mut cube = Cube::new(ObjectNameTag::new("Cube"), 2.0, WHITE);
+    cube.translate(&Vec3d::new(-1.0, 0.0, 0.5));
+    let mut camera = MyCamera::new("Camera");
+    camera.init(800, 600, 90.0, 0.1, 100.0);
+    camera.set_cull_backfaces(false);
+
+    let mut out = vec![];
+    camera.project_to(&cube, &mut out);
+    assert!(out.len() > 12);
+
+    for _ in 0..3 {
+        out.clear();
+        let before = ALLOCATIONS.with(|count| count.get());
+        camera.project_to(&cube, &mut out);
+        assert_eq!(ALLOCATIONS.with(|count| count.get()), before);
+    }
+}